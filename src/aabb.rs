@@ -0,0 +1,101 @@
+use nalgebra::Vector3;
+
+use crate::ray::Ray;
+
+#[derive(Clone, Copy, Debug)]
+pub struct AxisAlignedBoundingBox {
+    min: Vector3<f64>,
+    max: Vector3<f64>,
+}
+
+impl AxisAlignedBoundingBox {
+    pub fn new(a: Vector3<f64>, b: Vector3<f64>) -> Self {
+        // Accept the corners in any order
+        Self {
+            min: a.inf(&b),
+            max: a.sup(&b),
+        }
+    }
+
+    pub fn surrounding(a: &Self, b: &Self) -> Self {
+        Self {
+            min: a.min.inf(&b.min),
+            max: a.max.sup(&b.max),
+        }
+    }
+
+    /// Overlap of both boxes, `None` if they're disjoint.
+    pub fn intersection(a: &Self, b: &Self) -> Option<Self> {
+        let min = a.min.sup(&b.min);
+        let max = a.max.inf(&b.max);
+        if (0..3).any(|axis| min[axis] > max[axis]) {
+            return None;
+        }
+        Some(Self { min, max })
+    }
+
+    pub fn min(&self) -> Vector3<f64> {
+        self.min
+    }
+
+    pub fn max(&self) -> Vector3<f64> {
+        self.max
+    }
+
+    pub fn centroid(&self) -> Vector3<f64> {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn extent(&self) -> Vector3<f64> {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.extent();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        self.extent().imax()
+    }
+
+    /// Grows any near-zero side so flat primitives still get hit by the slab test
+    pub fn padded(&self, delta: f64) -> Self {
+        let mut min = self.min;
+        let mut max = self.max;
+        for axis in 0..3 {
+            if max[axis] - min[axis] < delta {
+                min[axis] -= delta / 2.0;
+                max[axis] += delta / 2.0;
+            }
+        }
+        Self { min, max }
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.intersect(r, t_min, t_max).is_some()
+    }
+
+    /// Part of `[t_min, t_max]` the ray spends inside the box.
+    pub fn intersect(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> Option<(f64, f64)> {
+        let origin = r.origin();
+        let direction = r.direction();
+
+        for axis in 0..3 {
+            let inv_d = 1.0 / direction[axis];
+            let mut t0 = (self.min[axis] - origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return None;
+            }
+        }
+
+        Some((t_min, t_max))
+    }
+}
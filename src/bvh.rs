@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AxisAlignedBoundingBox,
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+};
+
+const SAH_BUCKETS: usize = 12;
+
+pub struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: AxisAlignedBoundingBox,
+}
+
+impl BvhNode {
+    /// Builds a hierarchy over `objects` using binned surface area heuristic splits.
+    ///
+    /// Every object must report a bounding box; unbounded objects (e.g. infinite planes)
+    /// have to be kept outside the hierarchy, see `HittableList::into_bvh`.
+    pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Self {
+        assert!(!objects.is_empty(), "Cannot build a BVH from an empty list");
+
+        let mut entries: Vec<(AxisAlignedBoundingBox, Arc<dyn Hittable>)> = objects
+            .into_iter()
+            .map(|object| {
+                let bbox = object
+                    .bounding_box()
+                    .expect("Every object in a BVH needs a bounding box");
+                (bbox, object)
+            })
+            .collect();
+
+        Self::build(&mut entries)
+    }
+
+    fn build(entries: &mut [(AxisAlignedBoundingBox, Arc<dyn Hittable>)]) -> Self {
+        let bbox = entries.iter().skip(1).fold(entries[0].0, |acc, (b, _)| {
+            AxisAlignedBoundingBox::surrounding(&acc, b)
+        });
+
+        let (left, right): (Arc<dyn Hittable>, Arc<dyn Hittable>) = match entries.len() {
+            1 => (Arc::clone(&entries[0].1), Arc::clone(&entries[0].1)),
+            2 => (Arc::clone(&entries[0].1), Arc::clone(&entries[1].1)),
+            _ => {
+                let mid = Self::partition(entries);
+                let (lower, upper) = entries.split_at_mut(mid);
+                (Arc::new(Self::build(lower)), Arc::new(Self::build(upper)))
+            }
+        };
+
+        Self { left, right, bbox }
+    }
+
+    /// Reorders `entries` and returns the split index, preferring the cheapest SAH bucket
+    /// boundary and falling back to a median split when the centroids are degenerate.
+    fn partition(entries: &mut [(AxisAlignedBoundingBox, Arc<dyn Hittable>)]) -> usize {
+        let centroid_bounds = entries.iter().skip(1).fold(
+            AxisAlignedBoundingBox::new(entries[0].0.centroid(), entries[0].0.centroid()),
+            |acc, (b, _)| {
+                AxisAlignedBoundingBox::surrounding(
+                    &acc,
+                    &AxisAlignedBoundingBox::new(b.centroid(), b.centroid()),
+                )
+            },
+        );
+        let axis = centroid_bounds.longest_axis();
+        let lo = centroid_bounds.min()[axis];
+        let span = centroid_bounds.max()[axis] - lo;
+
+        entries.sort_by(|a, b| a.0.centroid()[axis].total_cmp(&b.0.centroid()[axis]));
+
+        if span <= f64::EPSILON {
+            return entries.len() / 2;
+        }
+
+        let bucket_of = |b: &AxisAlignedBoundingBox| -> usize {
+            let offset = (b.centroid()[axis] - lo) / span;
+            ((offset * SAH_BUCKETS as f64) as usize).min(SAH_BUCKETS - 1)
+        };
+
+        let mut counts = [0usize; SAH_BUCKETS];
+        let mut bounds: [Option<AxisAlignedBoundingBox>; SAH_BUCKETS] = [None; SAH_BUCKETS];
+        for (b, _) in entries.iter() {
+            let i = bucket_of(b);
+            counts[i] += 1;
+            bounds[i] = Some(match bounds[i] {
+                Some(existing) => AxisAlignedBoundingBox::surrounding(&existing, b),
+                None => *b,
+            });
+        }
+
+        let merge = |range: &[Option<AxisAlignedBoundingBox>]| -> f64 {
+            range
+                .iter()
+                .flatten()
+                .copied()
+                .reduce(|a, b| AxisAlignedBoundingBox::surrounding(&a, &b))
+                .map_or(0.0, |b| b.surface_area())
+        };
+
+        let mut best_split = 0;
+        let mut best_cost = f64::INFINITY;
+        for split in 1..SAH_BUCKETS {
+            let left_count: usize = counts[..split].iter().sum();
+            let right_count: usize = counts[split..].iter().sum();
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+            let cost = merge(&bounds[..split]) * left_count as f64
+                + merge(&bounds[split..]) * right_count as f64;
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = split;
+            }
+        }
+
+        if best_split == 0 {
+            return entries.len() / 2;
+        }
+
+        // Entries are sorted along the axis, so the bucket boundary is a prefix length
+        entries
+            .iter()
+            .position(|(b, _)| bucket_of(b) >= best_split)
+            .unwrap_or(entries.len() / 2)
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+
+        let hit_left = self.left.hit(r, t_min, t_max);
+        let closest = hit_left.as_ref().map_or(t_max, |record| record.t);
+        let hit_right = self.right.hit(r, t_min, closest);
+
+        hit_right.or(hit_left)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return 1.0;
+        }
+
+        // Order doesn't matter along a shadow ray, so there's no closest hit to track
+        let left = self.left.transmittance(r, t_min, t_max);
+        if left == 0.0 {
+            return 0.0;
+        }
+        left * self.right.transmittance(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        Some(self.bbox)
+    }
+}
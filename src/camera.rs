@@ -1,8 +1,7 @@
 use nalgebra::Vector3;
-use rand::random;
 use std::f64::consts::PI;
 
-use crate::ray::Ray;
+use crate::{ray::Ray, utils::random_double};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Camera {
@@ -12,8 +11,9 @@ pub struct Camera {
     vertical: Vector3<f64>,
     u: Vector3<f64>, // Camera basis vectors
     v: Vector3<f64>,
-    w: Vector3<f64>,
     lens_radius: f64, // For depth of field
+    shutter_open: f64,
+    shutter_close: f64,
 }
 
 impl Camera {
@@ -47,14 +47,22 @@ impl Camera {
             vertical,
             u,
             v,
-            w,
             lens_radius: aperture / 2.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
     }
 
+    /// Spreads camera rays over the time interval the shutter is open, for motion blur.
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
     fn random_in_unit_disk() -> Vector3<f64> {
         loop {
-            let p = 2.0 * Vector3::new(random::<f64>(), random::<f64>(), 0.0)
+            let p = 2.0 * Vector3::new(random_double(), random_double(), 0.0)
                 - Vector3::new(1.0, 1.0, 0.0);
             if p.magnitude_squared() < 1.0 {
                 return p;
@@ -66,9 +74,12 @@ impl Camera {
         let rd = self.lens_radius * Self::random_in_unit_disk();
         let offset = self.u * rd.x + self.v * rd.y;
 
+        let time = self.shutter_open + random_double() * (self.shutter_close - self.shutter_open);
+
         Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            time,
         )
     }
 }
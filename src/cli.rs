@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::{output::OutputFormat, scene::RenderSettings, tonemap::ToneMapper};
+
+/// Command-line options. Render settings given here override the scene file.
+#[derive(Debug, Parser)]
+#[command(version, about = "Renders a TOML scene description with a path tracer")]
+pub struct Args {
+    /// Scene description to render
+    #[arg(default_value = "scenes/demo.toml")]
+    pub scene: PathBuf,
+
+    /// Output image path [default: render_<timestamp>.<format>]
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Output image format [default: from the output extension, else png]
+    #[arg(short, long, value_enum)]
+    pub format: Option<OutputFormat>,
+
+    /// Image width in pixels; keeps the scene's aspect ratio if no height is given
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Image height in pixels; keeps the scene's aspect ratio if no width is given
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Samples per pixel
+    #[arg(short, long)]
+    pub spp: Option<u32>,
+
+    /// Maximum number of bounces per path
+    #[arg(long)]
+    pub max_depth: Option<i32>,
+
+    /// Tone mapping operator for PNG/JPEG output
+    #[arg(short, long, value_enum)]
+    pub tonemap: Option<ToneMapper>,
+
+    /// Exposure compensation in stops (EV)
+    #[arg(short, long, allow_negative_numbers = true)]
+    pub exposure: Option<f64>,
+
+    /// Input level mapped to white by the reinhard-extended operator
+    #[arg(long)]
+    pub white_point: Option<f64>,
+
+    /// Number of render threads [default: one per logical core]
+    #[arg(short = 'j', long)]
+    pub threads: Option<usize>,
+
+    /// Random seed, for reproducible renders
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Don't show the progress bar
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+impl Args {
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        let aspect_ratio = settings.aspect_ratio();
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => {
+                settings.width = width;
+                settings.height = height;
+            }
+            (Some(width), None) => {
+                settings.width = width;
+                settings.height = ((width as f64 / aspect_ratio).round() as u32).max(2);
+            }
+            (None, Some(height)) => {
+                settings.width = ((height as f64 * aspect_ratio).round() as u32).max(2);
+                settings.height = height;
+            }
+            (None, None) => {}
+        }
+
+        if let Some(spp) = self.spp {
+            settings.samples_per_pixel = spp;
+        }
+        if let Some(max_depth) = self.max_depth {
+            settings.max_depth = max_depth;
+        }
+        if let Some(tonemap) = self.tonemap {
+            settings.tone_mapping = tonemap;
+        }
+        if let Some(exposure) = self.exposure {
+            settings.exposure = exposure;
+        }
+        if let Some(white_point) = self.white_point {
+            settings.white_point = white_point;
+        }
+    }
+
+    /// Resolves the output path and format from whichever of the two were given.
+    pub fn output(&self, timestamp: u64) -> (PathBuf, OutputFormat) {
+        let format = self
+            .format
+            .or_else(|| self.output.as_deref().and_then(OutputFormat::from_path))
+            .unwrap_or(OutputFormat::Png);
+        let path = self.output.clone().unwrap_or_else(|| {
+            PathBuf::from(format!("render_{}.{}", timestamp, format.extension()))
+        });
+        (path, format)
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use crate::{
+    aabb::AxisAlignedBoundingBox,
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+};
+
+/// How a `Csg` combines the regions enclosed by its operands.
+#[derive(Clone, Copy, Debug)]
+pub enum Operation {
+    Union,
+    Intersection,
+    /// The left region with the right one cut out of it
+    Difference,
+}
+
+impl Operation {
+    fn contains(self, inside_left: bool, inside_right: bool) -> bool {
+        match self {
+            Self::Union => inside_left || inside_right,
+            Self::Intersection => inside_left && inside_right,
+            Self::Difference => inside_left && !inside_right,
+        }
+    }
+}
+
+/// Step past a surface before looking for the next one, relative to `t`.
+const SURFACE_STEP: f64 = 1e-7;
+
+/// Successive surface crossings of one operand along a ray.
+struct Crossings<'a> {
+    object: &'a dyn Hittable,
+    next: Option<HitRecord>,
+    inside: bool,
+}
+
+impl<'a> Crossings<'a> {
+    fn new(object: &'a dyn Hittable, r: &Ray, t_min: f64, t_max: f64) -> Self {
+        let next = object.hit(r, t_min, t_max);
+        // Leaving through the first surface means the ray started inside
+        let inside = next.as_ref().is_some_and(|hit| !hit.front_face);
+        Self {
+            object,
+            next,
+            inside,
+        }
+    }
+
+    /// Crosses the pending surface, returning it and looking up the one after.
+    fn advance(&mut self, r: &Ray, t_max: f64) -> Option<HitRecord> {
+        let hit = self.next.take()?;
+        self.inside = !self.inside;
+        let t_next = hit.t + SURFACE_STEP * (1.0 + hit.t.abs());
+        self.next = self.object.hit(r, t_next, t_max);
+        Some(hit)
+    }
+}
+
+/// Constructive solid geometry: union, intersection or difference of two closed objects.
+///
+/// Walks the surfaces of both operands along the ray, tracking whether it's inside each,
+/// and stops at the first crossing that enters or leaves the combined region. Open
+/// surfaces like triangles or uncapped cylinders don't enclose anything and give odd results.
+pub struct Csg {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    operation: Operation,
+}
+
+impl Csg {
+    pub fn new(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>, operation: Operation) -> Self {
+        Self {
+            left,
+            right,
+            operation,
+        }
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut left = Crossings::new(self.left.as_ref(), r, t_min, t_max);
+        let mut right = Crossings::new(self.right.as_ref(), r, t_min, t_max);
+
+        loop {
+            let was_inside = self.operation.contains(left.inside, right.inside);
+            let left_first = match (&left.next, &right.next) {
+                (Some(a), Some(b)) => a.t <= b.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+            let mut hit = if left_first {
+                left.advance(r, t_max)?
+            } else {
+                right.advance(r, t_max)?
+            };
+
+            if self.operation.contains(left.inside, right.inside) != was_inside {
+                // Cut out surfaces face into the removed region
+                let outward = if hit.front_face {
+                    hit.normal
+                } else {
+                    -hit.normal
+                };
+                let flip = !left_first && matches!(self.operation, Operation::Difference);
+                hit.set_face_normal(r, if flip { -outward } else { outward });
+                return Some(hit);
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        let left = self.left.bounding_box();
+        let right = self.right.bounding_box();
+        match self.operation {
+            Operation::Union => Some(AxisAlignedBoundingBox::surrounding(&left?, &right?)),
+            // An empty intersection is never hit, any box will do
+            Operation::Intersection => match (left, right) {
+                (Some(a), Some(b)) => {
+                    Some(AxisAlignedBoundingBox::intersection(&a, &b).unwrap_or(a))
+                }
+                (a, b) => a.or(b),
+            },
+            Operation::Difference => left,
+        }
+    }
+}
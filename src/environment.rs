@@ -1,8 +1,37 @@
-use crate::ray::Ray;
+use crate::{
+    ray::Ray,
+    sampling::{cone_pdf, random_cone_direction, Onb},
+};
 use nalgebra::Vector3;
 
 pub trait Environment: Send + Sync {
     fn background_color(&self, ray: &Ray) -> Vector3<f64>;
+
+    /// Samples a direction towards a bright feature worth explicit light sampling
+    fn sample_direction(&self) -> Option<Vector3<f64>> {
+        None
+    }
+
+    /// Solid angle density of `sample_direction` producing `direction`
+    fn pdf(&self, _direction: &Vector3<f64>) -> f64 {
+        0.0
+    }
+}
+
+pub struct ConstantEnvironment {
+    color: Vector3<f64>,
+}
+
+impl ConstantEnvironment {
+    pub fn new(color: Vector3<f64>) -> Self {
+        Self { color }
+    }
+}
+
+impl Environment for ConstantEnvironment {
+    fn background_color(&self, _ray: &Ray) -> Vector3<f64> {
+        self.color
+    }
 }
 
 pub struct GradientEnvironment {
@@ -64,4 +93,18 @@ impl Environment for SkyEnvironment {
             self.sky_color * (1.0 + 0.2 * sun_influence)
         }
     }
+
+    fn sample_direction(&self) -> Option<Vector3<f64>> {
+        let cone = random_cone_direction(self.sun_angular_size.cos());
+        Some(Onb::new(&self.sun_direction).local(&cone))
+    }
+
+    fn pdf(&self, direction: &Vector3<f64>) -> f64 {
+        let cos_theta_max = self.sun_angular_size.cos();
+        if direction.normalize().dot(&self.sun_direction) > cos_theta_max {
+            cone_pdf(cos_theta_max)
+        } else {
+            0.0
+        }
+    }
 }
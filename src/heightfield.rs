@@ -0,0 +1,255 @@
+use std::{path::Path, sync::Arc};
+
+use nalgebra::Vector3;
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use crate::{
+    aabb::AxisAlignedBoundingBox,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    triangle,
+    utils::random,
+};
+
+/// Grid of heights in [0, 1], stored row by row. Rows run along +Z and columns along +X.
+pub struct HeightMap {
+    heights: Vec<f64>,
+    columns: usize,
+    rows: usize,
+}
+
+impl HeightMap {
+    /// Reads the luminance of an image as heights, white being the highest. The values are
+    /// used as stored, without undoing the sRGB curve.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("Failed to load height map '{}': {}", path.display(), e))?
+            .into_luma16();
+        let (columns, rows) = (image.width() as usize, image.height() as usize);
+        if columns < 2 || rows < 2 {
+            return Err(format!(
+                "Height map '{}' needs at least 2x2 pixels",
+                path.display()
+            ));
+        }
+        let heights = image
+            .pixels()
+            .map(|p| p[0] as f64 / u16::MAX as f64)
+            .collect();
+        Ok(Self {
+            heights,
+            columns,
+            rows,
+        })
+    }
+
+    /// Fractal Brownian motion noise over `columns` by `rows` samples, with `frequency`
+    /// features across the whole map at its coarsest octave.
+    pub fn fbm(columns: usize, rows: usize, frequency: f64, octaves: usize) -> Self {
+        let (columns, rows) = (columns.max(2), rows.max(2));
+        let noise = Fbm::<Perlin>::new(random())
+            .set_octaves(octaves)
+            .set_frequency(frequency);
+        let heights = (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let point = [
+                    i as f64 / (columns - 1) as f64,
+                    j as f64 / (rows - 1) as f64,
+                ];
+                (0.5 * (noise.get(point) + 1.0)).clamp(0.0, 1.0)
+            })
+            .collect();
+        Self {
+            heights,
+            columns,
+            rows,
+        }
+    }
+}
+
+/// Terrain over a height map, split into two triangles per grid cell. Rays walk the grid
+/// cells they cross in order (a 2D DDA), so only a handful of cells are ever tested.
+///
+/// `u` and `v` span the whole terrain, with `v` pointing to -Z like an image texture seen
+/// from above, so the image a height map came from lines up with it.
+pub struct Heightfield {
+    map: HeightMap,
+    // Corner at the lowest X and Z, at height 0
+    corner: Vector3<f64>,
+    // Extent along X and Z, and the height a value of 1 reaches
+    size: Vector3<f64>,
+    cell: (f64, f64),
+    bbox: AxisAlignedBoundingBox,
+    material: Arc<dyn Material>,
+}
+
+impl Heightfield {
+    /// Centers the terrain's footprint on `center`, with its base at `center.y`.
+    pub fn new(
+        map: HeightMap,
+        center: Vector3<f64>,
+        size: Vector3<f64>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let corner = center - Vector3::new(size.x / 2.0, 0.0, size.z / 2.0);
+        let cell = (
+            size.x / (map.columns - 1) as f64,
+            size.z / (map.rows - 1) as f64,
+        );
+        let (low, high) = map
+            .heights
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &h| {
+                (low.min(h), high.max(h))
+            });
+        let bbox = AxisAlignedBoundingBox::new(
+            corner + Vector3::new(0.0, low * size.y, 0.0),
+            corner + Vector3::new(size.x, high * size.y, size.z),
+        )
+        .padded(1e-4);
+
+        Self {
+            map,
+            corner,
+            size,
+            cell,
+            bbox,
+            material,
+        }
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.map.heights[j * self.map.columns + i] * self.size.y
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vector3<f64> {
+        self.corner
+            + Vector3::new(
+                i as f64 * self.cell.0,
+                self.height(i, j),
+                j as f64 * self.cell.1,
+            )
+    }
+
+    /// Smooth normal from the slopes to the neighbouring samples.
+    fn normal(&self, i: usize, j: usize) -> Vector3<f64> {
+        let (left, right) = (i.saturating_sub(1), (i + 1).min(self.map.columns - 1));
+        let (back, front) = (j.saturating_sub(1), (j + 1).min(self.map.rows - 1));
+        let dx =
+            (self.height(right, j) - self.height(left, j)) / ((right - left) as f64 * self.cell.0);
+        let dz =
+            (self.height(i, front) - self.height(i, back)) / ((front - back) as f64 * self.cell.1);
+        Vector3::new(-dx, 1.0, -dz).normalize()
+    }
+
+    fn uv(&self, i: usize, j: usize) -> (f64, f64) {
+        (
+            i as f64 / (self.map.columns - 1) as f64,
+            1.0 - j as f64 / (self.map.rows - 1) as f64,
+        )
+    }
+
+    /// Closest hit on the two triangles of cell `(i, j)`.
+    fn hit_cell(&self, r: &Ray, i: usize, j: usize, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut closest: Option<HitRecord> = None;
+        let mut closest_so_far = t_max;
+        for [a, b, c] in [[0, 2, 1], [0, 3, 2]] {
+            let indices = [corners[a], corners[b], corners[c]];
+            let positions = indices.map(|(i, j)| self.vertex(i, j));
+            let [p0, p1, p2] = &positions;
+            if let Some((t, b1, b2)) = triangle::intersect(r, t_min, closest_so_far, p0, p1, p2) {
+                closest_so_far = t;
+                closest = Some(triangle::hit_record(
+                    r,
+                    t,
+                    b1,
+                    b2,
+                    positions,
+                    Some(indices.map(|(i, j)| self.normal(i, j))),
+                    Some(indices.map(|(i, j)| self.uv(i, j))),
+                    &self.material,
+                ));
+            }
+        }
+        closest
+    }
+
+    /// Lowest and highest of the four corners of cell `(i, j)`.
+    fn cell_range(&self, i: usize, j: usize) -> (f64, f64) {
+        [(i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1)]
+            .iter()
+            .map(|&(i, j)| self.height(i, j))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), h| {
+                (low.min(h), high.max(h))
+            })
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t_enter, t_exit) = self.bbox.intersect(r, t_min, t_max)?;
+        let origin = r.origin() - self.corner;
+        let direction = r.direction();
+        let cells = [self.map.columns - 1, self.map.rows - 1];
+        let cell_size = [self.cell.0, self.cell.1];
+
+        // Walk X and Z as axes 0 and 1 of the grid
+        let start = origin + t_enter * direction;
+        let position = [start.x, start.z];
+        let heading = [direction.x, direction.z];
+        let mut cell = [0; 2];
+        let mut step = [0i64; 2];
+        let mut t_next = [f64::INFINITY; 2];
+        let mut t_delta = [f64::INFINITY; 2];
+        for axis in 0..2 {
+            let index = (position[axis] / cell_size[axis]).floor().max(0.0) as usize;
+            cell[axis] = index.min(cells[axis] - 1);
+            if heading[axis] > 0.0 {
+                step[axis] = 1;
+                let boundary = (cell[axis] + 1) as f64 * cell_size[axis];
+                t_next[axis] = t_enter + (boundary - position[axis]) / heading[axis];
+                t_delta[axis] = cell_size[axis] / heading[axis];
+            } else if heading[axis] < 0.0 {
+                step[axis] = -1;
+                let boundary = cell[axis] as f64 * cell_size[axis];
+                t_next[axis] = t_enter + (boundary - position[axis]) / heading[axis];
+                t_delta[axis] = -cell_size[axis] / heading[axis];
+            }
+        }
+
+        let mut t_cell = t_enter;
+        loop {
+            let t_leave = t_next[0].min(t_next[1]).min(t_exit);
+
+            // Skip cells whose terrain lies entirely above or below the ray's span over them
+            let (low, high) = self.cell_range(cell[0], cell[1]);
+            let y_enter = origin.y + t_cell * direction.y;
+            let y_leave = origin.y + t_leave * direction.y;
+            let margin = 1e-6 * self.size.y.abs().max(1.0);
+            if y_enter.min(y_leave) <= high + margin && y_enter.max(y_leave) >= low - margin {
+                if let Some(hit) = self.hit_cell(r, cell[0], cell[1], t_min, t_max) {
+                    return Some(hit);
+                }
+            }
+
+            if t_leave >= t_exit {
+                return None;
+            }
+            let axis = if t_next[0] < t_next[1] { 0 } else { 1 };
+            let next = cell[axis] as i64 + step[axis];
+            if next < 0 || next >= cells[axis] as i64 {
+                return None;
+            }
+            cell[axis] = next as usize;
+            t_cell = t_next[axis];
+            t_next[axis] += t_delta[axis];
+        }
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        Some(self.bbox)
+    }
+}
@@ -3,14 +3,37 @@ use std::sync::Arc;
 use nalgebra::Vector3;
 
 use crate::{
-    material::{Lambertian, Material},
-    ray::Ray,
-    texture::SolidColor,
+    aabb::AxisAlignedBoundingBox, bvh::BvhNode, material::Material, ray::Ray, sampling::Onb,
+    utils::random_double,
 };
 
 pub trait Hittable: Send + Sync {
     // Make it thread-safe
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+    /// `None` for unbounded objects, which are then kept out of the BVH
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox>;
+
+    /// Fraction of light passing through the object along `r` between `t_min` and `t_max`.
+    /// Surfaces block it completely, volumes override this to let some through.
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        if self.hit(r, t_min, t_max).is_some() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+
+    /// Solid angle density of `random_direction` producing `direction` from `origin`.
+    /// Objects that can't be sampled as lights report 0.
+    fn pdf_value(&self, _origin: &Vector3<f64>, _direction: &Vector3<f64>) -> f64 {
+        0.0
+    }
+
+    /// Samples a (not necessarily normalized) direction from `origin` towards the object.
+    fn random_direction(&self, _origin: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(1.0, 0.0, 0.0)
+    }
 }
 
 #[derive(Clone)]
@@ -18,11 +41,27 @@ pub struct HitRecord {
     pub p: Vector3<f64>,
     pub normal: Vector3<f64>,
     pub t: f64,
+    /// Surface parameterization at the hit, passed to `Texture::value`
+    pub u: f64,
+    pub v: f64,
+    /// Partial derivatives of the surface position along `u` and `v`
+    pub dpdu: Vector3<f64>,
+    pub dpdv: Vector3<f64>,
     pub front_face: bool,
     pub material: Arc<dyn Material>,
 }
 
 impl HitRecord {
+    /// Local frame around the shading normal, oriented along the surface tangents.
+    pub fn shading_frame(&self) -> Onb {
+        // dpdv stands in at degenerate points such as sphere poles
+        if self.dpdu.magnitude_squared() > 1e-16 {
+            Onb::from_normal_tangent(&self.normal, &self.dpdu)
+        } else {
+            Onb::from_normal_tangent(&self.normal, &self.dpdv)
+        }
+    }
+
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vector3<f64>) {
         self.front_face = r.direction().dot(&outward_normal) < 0.0;
         self.normal = if self.front_face {
@@ -34,7 +73,7 @@ impl HitRecord {
 }
 
 pub struct HittableList {
-    objects: Vec<Box<dyn Hittable>>,
+    objects: Vec<Arc<dyn Hittable>>,
 }
 
 impl HittableList {
@@ -44,37 +83,88 @@ impl HittableList {
         }
     }
 
-    pub fn add(&mut self, object: Box<dyn Hittable>) {
+    pub fn add(&mut self, object: Arc<dyn Hittable>) {
         self.objects.push(object);
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Moves every bounded object into a `BvhNode`, keeping unbounded ones alongside it.
+    pub fn into_bvh(self) -> Arc<dyn Hittable> {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = self
+            .objects
+            .into_iter()
+            .partition(|object| object.bounding_box().is_some());
+
+        if bounded.is_empty() {
+            return Arc::new(Self { objects: unbounded });
+        }
+
+        let bvh: Arc<dyn Hittable> = Arc::new(BvhNode::new(bounded));
+        if unbounded.is_empty() {
+            return bvh;
+        }
+
+        let mut objects = vec![bvh];
+        objects.extend(unbounded);
+        Arc::new(Self { objects })
+    }
 }
 
 impl Hittable for HittableList {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut temp_record = HitRecord {
-            p: Vector3::new(0.0, 0.0, 0.0),
-            normal: Vector3::new(0.0, 0.0, 0.0),
-            t: 0.0,
-            front_face: false,
-            material: Arc::new(Lambertian::new(Box::new(
-                SolidColor::new(Vector3::new(0.2, 0.3, 0.1)), // Dark green
-            ))),
-        };
-        let mut hit_anything = false;
+        let mut closest: Option<HitRecord> = None;
         let mut closest_so_far = t_max;
 
         for object in self.objects.iter() {
             if let Some(record) = object.hit(r, t_min, closest_so_far) {
-                hit_anything = true;
                 closest_so_far = record.t;
-                temp_record = record;
+                closest = Some(record);
             }
         }
 
-        if hit_anything {
-            Some(temp_record)
-        } else {
-            None
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        let mut boxes = self.objects.iter().map(|object| object.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |acc, bbox| {
+            bbox.map(|b| AxisAlignedBoundingBox::surrounding(&acc, &b))
+        })
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let mut transmittance = 1.0;
+        for object in self.objects.iter() {
+            transmittance *= object.transmittance(r, t_min, t_max);
+            if transmittance == 0.0 {
+                break;
+            }
+        }
+        transmittance
+    }
+
+    fn pdf_value(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> f64 {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum();
+        sum / self.objects.len() as f64
+    }
+
+    fn random_direction(&self, origin: &Vector3<f64>) -> Vector3<f64> {
+        if self.objects.is_empty() {
+            return Vector3::new(1.0, 0.0, 0.0);
         }
+        let index =
+            ((random_double() * self.objects.len() as f64) as usize).min(self.objects.len() - 1);
+        self.objects[index].random_direction(origin)
     }
 }
@@ -1,10 +1,15 @@
+use std::{f64::consts::PI, sync::Arc};
+
 use nalgebra::Vector3;
-use rand::Rng;
+use serde::Deserialize;
 
 use crate::{
     hittable::HitRecord,
+    microfacet::{fresnel_conductor, Ggx},
     ray::Ray,
+    sampling::{random_cosine_direction, Onb},
     texture::{SolidColor, Texture},
+    utils::random_double,
 };
 
 fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
@@ -21,54 +26,203 @@ fn refract(uv: &Vector3<f64>, n: &Vector3<f64>, etai_over_etat: f64) -> Vector3<
     (r_out_perp + r_out_parallel).normalize() // Normalize the result
 }
 
+/// An outgoing direction chosen by `Material::sample`.
+pub struct ScatterSample {
+    pub scattered: Ray,
+    /// BSDF times cosine over pdf, i.e. the factor the incoming radiance is scaled by
+    pub attenuation: Vector3<f64>,
+    /// Solid angle density of the sample; meaningless for specular samples
+    pub pdf: f64,
+    /// Sampled from a delta (or otherwise unevaluable) lobe that `eval` and `pdf` don't cover
+    pub is_specular: bool,
+}
+
 pub trait Material: Send + Sync {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f64>)>;
+    fn sample(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatterSample>;
+
+    /// BSDF times the cosine term for light arriving from `direction`. Specular lobes
+    /// contribute nothing here.
+    fn eval(&self, _ray: &Ray, _hit: &HitRecord, _direction: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
+
+    /// Solid angle density with which `sample` picks `direction`, excluding specular lobes.
+    fn pdf(&self, _ray: &Ray, _hit: &HitRecord, _direction: &Vector3<f64>) -> f64 {
+        0.0
+    }
+
+    /// Radiance given off by the surface itself; black for everything but lights
+    fn emitted(&self, _u: f64, _v: f64, _p: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
 }
 
 pub struct Lambertian {
-    albedo: Box<dyn Texture>,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
-    pub fn new(albedo: Box<dyn Texture>) -> Self {
+    pub fn new(albedo: Arc<dyn Texture>) -> Self {
         Self { albedo }
     }
 
     pub fn from_color(color: Vector3<f64>) -> Self {
         Self {
-            albedo: Box::new(SolidColor::new(color)),
+            albedo: Arc::new(SolidColor::new(color)),
         }
     }
 }
 
 pub struct Metal {
-    albedo: Vector3<f64>,
-    fuzz: f64,
+    albedo: Arc<dyn Texture>,
+    /// Read as a scalar from the texture's mean channel, clamped to [0, 1]
+    fuzz: Arc<dyn Texture>,
 }
 
 impl Metal {
-    pub fn new(albedo: Vector3<f64>, fuzz: f64) -> Self {
-        Self {
-            albedo,
-            fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
-        }
+    pub fn new(albedo: Arc<dyn Texture>, fuzz: Arc<dyn Texture>) -> Self {
+        Self { albedo, fuzz }
+    }
+
+    pub fn from_color(albedo: Vector3<f64>, fuzz: f64) -> Self {
+        Self::new(
+            Arc::new(SolidColor::new(albedo)),
+            Arc::new(SolidColor::new(Vector3::repeat(fuzz))),
+        )
     }
 }
 
+/// Wavelengths in micrometers standing in for the red, green and blue channels.
+const CHANNEL_WAVELENGTHS: [f64; 3] = [0.630, 0.532, 0.465];
+
 pub struct Dielectric {
     ir: f64,
+    /// Beer-Lambert absorption coefficient per unit of distance travelled inside
+    absorption: Vector3<f64>,
+    /// Cauchy coefficients `(a, b)` of `n = a + b / wavelength^2`, if dispersive
+    cauchy: Option<(f64, f64)>,
 }
 
 impl Dielectric {
     pub fn new(ir: f64) -> Self {
-        Self { ir }
+        Self {
+            ir,
+            absorption: Vector3::new(0.0, 0.0, 0.0),
+            cauchy: None,
+        }
+    }
+
+    /// Tints the glass, more strongly the further light travels through it.
+    pub fn with_absorption(mut self, absorption: Vector3<f64>) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Makes the IOR vary with wavelength, `ir` being the value at the sodium d-line.
+    /// Lower Abbe numbers disperse more; crown glass is around 60, dense flint around 30.
+    pub fn with_abbe_number(mut self, abbe: f64) -> Self {
+        let (d, f, c) = (0.5876, 0.4861, 0.6563);
+        let b = (self.ir - 1.0) / (abbe * (1.0 / (f * f) - 1.0 / (c * c)));
+        self.cauchy = Some((self.ir - b / (d * d), b));
+        self
+    }
+}
+
+/// Measured complex IOR of common metals, at roughly 650, 550 and 450 nm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    #[serde(alias = "aluminum")]
+    Aluminium,
+    Silver,
+}
+
+impl ConductorPreset {
+    /// `(eta, k)` per color channel.
+    pub fn ior(self) -> (Vector3<f64>, Vector3<f64>) {
+        let (eta, k) = match self {
+            Self::Gold => ([0.143, 0.374, 1.442], [3.983, 2.385, 1.603]),
+            Self::Copper => ([0.200, 0.924, 1.102], [3.912, 2.452, 2.142]),
+            Self::Aluminium => ([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]),
+            Self::Silver => ([0.155, 0.117, 0.138], [4.828, 3.122, 2.147]),
+        };
+        (Vector3::from(eta), Vector3::from(k))
+    }
+}
+
+/// GGX microfacet metal with a complex index of refraction.
+pub struct Conductor {
+    eta: Vector3<f64>,
+    k: Vector3<f64>,
+    roughness: Arc<dyn Texture>,
+}
+
+impl Conductor {
+    pub fn new(eta: Vector3<f64>, k: Vector3<f64>, roughness: Arc<dyn Texture>) -> Self {
+        Self { eta, k, roughness }
+    }
+
+    pub fn from_preset(preset: ConductorPreset, roughness: Arc<dyn Texture>) -> Self {
+        let (eta, k) = preset.ior();
+        Self::new(eta, k, roughness)
+    }
+}
+
+/// GGX microfacet glass, reflecting and refracting through rough interfaces.
+pub struct RoughDielectric {
+    ir: f64,
+    roughness: Arc<dyn Texture>,
+}
+
+impl RoughDielectric {
+    pub fn new(ir: f64, roughness: Arc<dyn Texture>) -> Self {
+        Self { ir, roughness }
+    }
+
+    /// Ratio of the IOR on the far side of the surface over the near one.
+    fn eta(&self, hit_record: &HitRecord) -> f64 {
+        if hit_record.front_face {
+            self.ir
+        } else {
+            1.0 / self.ir
+        }
+    }
+}
+
+/// Shading frame, outgoing direction in that frame and distribution at a hit.
+fn microfacet_frame(
+    ray_in: &Ray,
+    hit_record: &HitRecord,
+    roughness: &dyn Texture,
+) -> (Onb, Vector3<f64>, Ggx) {
+    let frame = hit_record.shading_frame();
+    let wo = frame.to_local(&-ray_in.direction().normalize());
+    let roughness = roughness
+        .value(hit_record.u, hit_record.v, &hit_record.p)
+        .mean();
+    (frame, wo, Ggx::from_roughness(roughness))
+}
+
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+    intensity: f64,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Arc<dyn Texture>, intensity: f64) -> Self {
+        Self { emit, intensity }
+    }
+
+    pub fn from_color(color: Vector3<f64>) -> Self {
+        Self::new(Arc::new(SolidColor::new(color)), 1.0)
     }
 }
 
 fn random_in_unit_sphere() -> Vector3<f64> {
-    let mut rng = rand::thread_rng();
     loop {
-        let p = 2.0 * Vector3::new(rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>())
+        let p = 2.0 * Vector3::new(random_double(), random_double(), random_double())
             - Vector3::new(1.0, 1.0, 1.0);
         if p.magnitude_squared() < 1.0 {
             return p;
@@ -81,23 +235,56 @@ fn reflect(v: &Vector3<f64>, n: &Vector3<f64>) -> Vector3<f64> {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Vector3<f64>)> {
-        let scatter_direction = hit_record.normal + random_in_unit_sphere().normalize();
-        let scattered = Ray::new(hit_record.p, scatter_direction);
-        let attenuation = self.albedo.value(0.0, 0.0, &hit_record.p); // We'll add proper UV coordinates later
-        Some((scattered, attenuation))
+    fn sample(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterSample> {
+        let direction = hit_record.shading_frame().local(&random_cosine_direction());
+        let pdf = self.pdf(ray_in, hit_record, &direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        // With cosine-weighted sampling the cosine and 1/pi cancel against the pdf
+        Some(ScatterSample {
+            scattered: Ray::new(hit_record.p, direction, ray_in.time()),
+            attenuation: self.albedo.value(hit_record.u, hit_record.v, &hit_record.p),
+            pdf,
+            is_specular: false,
+        })
+    }
+
+    fn eval(
+        &self,
+        _ray_in: &Ray,
+        hit_record: &HitRecord,
+        direction: &Vector3<f64>,
+    ) -> Vector3<f64> {
+        let cosine = hit_record.normal.dot(&direction.normalize()).max(0.0);
+        self.albedo.value(hit_record.u, hit_record.v, &hit_record.p) * (cosine / PI)
+    }
+
+    fn pdf(&self, _ray_in: &Ray, hit_record: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        let cosine = hit_record.normal.dot(&direction.normalize());
+        cosine.max(0.0) / PI
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Vector3<f64>)> {
+    fn sample(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterSample> {
+        let (u, v, p) = (hit_record.u, hit_record.v, &hit_record.p);
+        let fuzz = self.fuzz.value(u, v, p).mean().clamp(0.0, 1.0);
         let reflected = reflect(&ray_in.direction().normalize(), &hit_record.normal);
         let scattered = Ray::new(
             hit_record.p,
-            reflected + self.fuzz * random_in_unit_sphere(),
+            reflected + fuzz * random_in_unit_sphere(),
+            ray_in.time(),
         );
         if scattered.direction().dot(&hit_record.normal) > 0.0 {
-            Some((scattered, self.albedo))
+            // Fuzzed reflection has no closed-form density, so it's treated as specular
+            Some(ScatterSample {
+                scattered,
+                attenuation: self.albedo.value(u, v, p),
+                pdf: 0.0,
+                is_specular: true,
+            })
         } else {
             None
         }
@@ -105,26 +292,120 @@ impl Material for Metal {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Ray, Vector3<f64>)> {
-        let refraction_ratio = if hit_record.front_face {
-            1.0 / self.ir
-        } else {
-            self.ir
+    fn sample(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterSample> {
+        // A dispersive path follows a single channel, picked at random at its first dispersive
+        // hit and weighted by 3 there for the two it leaves out
+        let (ir, mut attenuation, channel) = match self.cauchy {
+            Some((a, b)) => {
+                let (channel, scale) = match ray_in.channel() {
+                    Some(channel) => (channel, 1.0),
+                    None => (((random_double() * 3.0) as usize).min(2), 3.0),
+                };
+                let wavelength = CHANNEL_WAVELENGTHS[channel];
+                let mut weight = Vector3::new(0.0, 0.0, 0.0);
+                weight[channel] = scale;
+                (a + b / (wavelength * wavelength), weight, Some(channel))
+            }
+            None => (self.ir, Vector3::new(1.0, 1.0, 1.0), None),
         };
 
+        if !hit_record.front_face {
+            // Leaving the medium, so the ray travelled through it since the last hit
+            let distance = hit_record.t * ray_in.direction().magnitude();
+            attenuation.component_mul_assign(&self.absorption.map(|a| (-a * distance).exp()));
+        }
+
+        let refraction_ratio = if hit_record.front_face { 1.0 / ir } else { ir };
+
         let unit_direction = ray_in.direction().normalize();
         let cos_theta = (-unit_direction).dot(&hit_record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let direction =
-            if cannot_refract || reflectance(cos_theta, refraction_ratio) > rand::random::<f64>() {
+            if cannot_refract || reflectance(cos_theta, refraction_ratio) > random_double() {
                 reflect(&unit_direction, &hit_record.normal)
             } else {
                 refract(&unit_direction, &hit_record.normal, refraction_ratio)
             };
 
-        let scattered = Ray::new(hit_record.p, direction);
-        Some((scattered, Vector3::new(1.0, 1.0, 1.0)))
+        let scattered = Ray::new(hit_record.p, direction, ray_in.time());
+        Some(ScatterSample {
+            scattered: match channel {
+                Some(channel) => scattered.with_channel(channel),
+                None => scattered,
+            },
+            attenuation,
+            pdf: 0.0,
+            is_specular: true,
+        })
+    }
+}
+
+impl Material for Conductor {
+    fn sample(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterSample> {
+        let (frame, wo, ggx) = microfacet_frame(ray_in, hit_record, self.roughness.as_ref());
+        let wi = ggx.sample_reflection(&wo)?;
+        let (wh, value, pdf) = ggx.reflection(&wo, &wi)?;
+        Some(ScatterSample {
+            scattered: Ray::new(hit_record.p, frame.local(&wi), ray_in.time()),
+            attenuation: fresnel_conductor(wo.dot(&wh), &self.eta, &self.k) * (value / pdf),
+            pdf,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        let (frame, wo, ggx) = microfacet_frame(ray_in, hit_record, self.roughness.as_ref());
+        match ggx.reflection(&wo, &frame.to_local(&direction.normalize())) {
+            Some((wh, value, _)) => fresnel_conductor(wo.dot(&wh), &self.eta, &self.k) * value,
+            None => Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        let (frame, wo, ggx) = microfacet_frame(ray_in, hit_record, self.roughness.as_ref());
+        ggx.reflection(&wo, &frame.to_local(&direction.normalize()))
+            .map_or(0.0, |(_, _, pdf)| pdf)
+    }
+}
+
+impl Material for RoughDielectric {
+    fn sample(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterSample> {
+        let (frame, wo, ggx) = microfacet_frame(ray_in, hit_record, self.roughness.as_ref());
+        let eta = self.eta(hit_record);
+        let wi = ggx.sample_dielectric(&wo, eta)?;
+        let (value, pdf) = ggx.dielectric(&wo, &wi, eta);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterSample {
+            scattered: Ray::new(hit_record.p, frame.local(&wi), ray_in.time()),
+            attenuation: Vector3::repeat(value / pdf),
+            pdf,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        let (frame, wo, ggx) = microfacet_frame(ray_in, hit_record, self.roughness.as_ref());
+        let wi = frame.to_local(&direction.normalize());
+        Vector3::repeat(ggx.dielectric(&wo, &wi, self.eta(hit_record)).0)
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        let (frame, wo, ggx) = microfacet_frame(ray_in, hit_record, self.roughness.as_ref());
+        let wi = frame.to_local(&direction.normalize());
+        ggx.dielectric(&wo, &wi, self.eta(hit_record)).1
+    }
+}
+
+impl Material for DiffuseLight {
+    fn sample(&self, _ray_in: &Ray, _hit_record: &HitRecord) -> Option<ScatterSample> {
+        None
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Vector3<f64>) -> Vector3<f64> {
+        self.intensity * self.emit.value(u, v, p)
     }
 }
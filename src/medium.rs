@@ -0,0 +1,434 @@
+//! Participating media and the phase functions that scatter light inside them.
+
+use std::{f64::consts::PI, fs, path::Path, sync::Arc};
+
+use nalgebra::Vector3;
+
+use crate::{
+    aabb::AxisAlignedBoundingBox,
+    hittable::{HitRecord, Hittable},
+    material::{Material, ScatterSample},
+    ray::Ray,
+    sampling::{random_unit_vector, Onb},
+    texture::{turbulence, Texture},
+    utils::{random, random_double},
+};
+
+/// Homogeneous volume filling a convex `boundary`, scattering with `phase_function`.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function,
+        }
+    }
+}
+
+/// Parametric range `[t_enter, t_exit)` of `r` inside `boundary`, clipped to `[t_min, t_max]`.
+/// Also works for rays starting inside the boundary.
+fn boundary_span(boundary: &dyn Hittable, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+    let entry = boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?;
+    let exit = boundary.hit(r, entry.t + 0.0001, f64::INFINITY)?;
+
+    let t_enter = entry.t.max(t_min).max(0.0);
+    let t_exit = exit.t.min(t_max);
+    (t_enter < t_exit).then_some((t_enter, t_exit))
+}
+
+/// Scattering event at `t` inside a medium.
+fn medium_record(r: &Ray, t: f64, phase_function: &Arc<dyn Material>) -> HitRecord {
+    HitRecord {
+        p: r.at(t),
+        normal: Vector3::new(1.0, 0.0, 0.0), // Arbitrary, phase functions don't use it
+        t,
+        u: 0.0,
+        v: 0.0,
+        dpdu: Vector3::new(0.0, 0.0, 0.0),
+        dpdv: Vector3::new(0.0, 0.0, 0.0),
+        front_face: true,
+        material: Arc::clone(phase_function),
+    }
+}
+
+/// Samples an exponentially distributed free-flight distance for a unit density.
+fn free_flight() -> f64 {
+    -(1.0 - random_double()).ln()
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t_enter, t_exit) = boundary_span(self.boundary.as_ref(), r, t_min, t_max)?;
+
+        let ray_length = r.direction().magnitude();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * random_double().ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        Some(medium_record(r, t, &self.phase_function))
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match boundary_span(self.boundary.as_ref(), r, t_min, t_max) {
+            Some((t_enter, t_exit)) => {
+                let distance_inside = (t_exit - t_enter) * r.direction().magnitude();
+                (distance_inside / self.neg_inv_density).exp()
+            }
+            None => 1.0,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Spatially varying density of a `HeterogeneousMedium`.
+pub trait DensityField: Send + Sync {
+    fn density(&self, p: &Vector3<f64>) -> f64;
+
+    /// Upper bound of `density`, the majorant for delta and ratio tracking. Tighter bounds
+    /// mean fewer wasted steps.
+    fn max_density(&self) -> f64;
+}
+
+/// Fractal Perlin noise, the same turbulence `MarbleTexture` uses, for clouds and smoke.
+pub struct NoiseDensity {
+    noise: noise::Perlin,
+    scale: f64,
+    octaves: u32,
+}
+
+impl NoiseDensity {
+    pub fn new(scale: f64, octaves: u32) -> Self {
+        Self {
+            noise: noise::Perlin::new(random()),
+            scale,
+            octaves: octaves.max(1),
+        }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: &Vector3<f64>) -> f64 {
+        turbulence(&self.noise, &(p * self.scale), self.octaves).min(self.max_density())
+    }
+
+    fn max_density(&self) -> f64 {
+        // Sum of the octave weights, each octave being within [-1, 1]
+        2.0 - 0.5f64.powi(self.octaves as i32 - 1)
+    }
+}
+
+/// Dense voxel grid in Mitsuba's `.vol` format, trilinearly interpolated between voxels
+/// and zero outside the grid's bounding box.
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    bbox: AxisAlignedBoundingBox,
+    data: Vec<f32>,
+    max_density: f64,
+}
+
+impl VoxelGrid {
+    /// Only single channel float32 volumes (encoding 1) are supported; further channels
+    /// are ignored.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes =
+            fs::read(path).map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        let error = |message: &str| format!("{}: {}", path.display(), message);
+
+        if bytes.len() < 48 || &bytes[0..3] != b"VOL" || bytes[3] != 3 {
+            return Err(error("not a version 3 .vol file"));
+        }
+        let int = |offset: usize| {
+            i32::from_le_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"))
+        };
+        let float = |offset: usize| {
+            f32::from_le_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes")) as f64
+        };
+
+        if int(4) != 1 {
+            return Err(error("only float32 encoding is supported"));
+        }
+        let (x, y, z, channels) = (int(8), int(12), int(16), int(20));
+        if x < 1 || y < 1 || z < 1 || channels < 1 {
+            return Err(error("invalid resolution"));
+        }
+        let resolution = [x as usize, y as usize, z as usize];
+        let channels = channels as usize;
+        let bbox = AxisAlignedBoundingBox::new(
+            Vector3::new(float(24), float(28), float(32)),
+            Vector3::new(float(36), float(40), float(44)),
+        );
+
+        // Header values are untrusted, so the expected size must not overflow
+        let too_large = || error("resolution is too large");
+        let count = resolution
+            .iter()
+            .try_fold(1usize, |count, &n| count.checked_mul(n))
+            .ok_or_else(too_large)?;
+        let expected_len = count
+            .checked_mul(channels)
+            .and_then(|n| n.checked_mul(4))
+            .and_then(|n| n.checked_add(48))
+            .ok_or_else(too_large)?;
+        if bytes.len() < expected_len {
+            return Err(error("file is truncated"));
+        }
+        let data: Vec<f32> = (0..count)
+            .map(|i| float(48 + i * channels * 4) as f32)
+            .collect();
+        let max_density = data.iter().fold(0.0f32, |max, &d| max.max(d)) as f64;
+
+        Ok(Self {
+            resolution,
+            bbox,
+            data,
+            max_density,
+        })
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f64 {
+        let [nx, ny, _] = self.resolution;
+        self.data[(z * ny + y) * nx + x].max(0.0) as f64
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: &Vector3<f64>) -> f64 {
+        let min = self.bbox.min();
+        let extent = self.bbox.extent();
+
+        // Voxels sit on the corners of the grid, spanning the whole bounding box
+        let mut base = [0; 3];
+        let mut frac = [0.0; 3];
+        for axis in 0..3 {
+            let t = (p[axis] - min[axis]) / extent[axis];
+            if !(0.0..=1.0).contains(&t) {
+                return 0.0;
+            }
+            let cells = self.resolution[axis] - 1;
+            let x = t * cells as f64;
+            base[axis] = (x.floor() as usize).min(cells.saturating_sub(1));
+            frac[axis] = if cells > 0 {
+                x - base[axis] as f64
+            } else {
+                0.0
+            };
+        }
+
+        let mut density = 0.0;
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0; 3];
+            for axis in 0..3 {
+                let upper = corner >> axis & 1 == 1;
+                weight *= if upper { frac[axis] } else { 1.0 - frac[axis] };
+                index[axis] = (base[axis] + upper as usize).min(self.resolution[axis] - 1);
+            }
+            if weight > 0.0 {
+                density += weight * self.voxel(index[0], index[1], index[2]);
+            }
+        }
+        density
+    }
+
+    fn max_density(&self) -> f64 {
+        self.max_density
+    }
+}
+
+/// Volume with a spatially varying density inside `boundary`. Scattering distances are
+/// sampled with delta tracking and shadow rays estimate transmittance with ratio tracking,
+/// both against the field's maximum density.
+pub struct HeterogeneousMedium {
+    boundary: Arc<dyn Hittable>,
+    field: Arc<dyn DensityField>,
+    density_scale: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        field: Arc<dyn DensityField>,
+        density_scale: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            boundary,
+            field,
+            density_scale,
+            phase_function,
+        }
+    }
+
+    fn majorant(&self) -> f64 {
+        self.field.max_density() * self.density_scale
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t_enter, t_exit) = boundary_span(self.boundary.as_ref(), r, t_min, t_max)?;
+        let majorant = self.majorant();
+        if majorant <= 0.0 {
+            return None;
+        }
+
+        // Delta tracking: tentative collisions against the majorant, accepted in proportion
+        // to the actual density and otherwise treated as null collisions
+        let step = 1.0 / (majorant * r.direction().magnitude());
+        let mut t = t_enter;
+        loop {
+            t += free_flight() * step;
+            if t >= t_exit {
+                return None;
+            }
+            let density = self.field.density(&r.at(t)) * self.density_scale;
+            if random_double() * majorant < density {
+                return Some(medium_record(r, t, &self.phase_function));
+            }
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        let Some((t_enter, t_exit)) = boundary_span(self.boundary.as_ref(), r, t_min, t_max) else {
+            return 1.0;
+        };
+        let majorant = self.majorant();
+        if majorant <= 0.0 {
+            return 1.0;
+        }
+
+        // Ratio tracking, with Russian roulette once little light is left
+        let step = 1.0 / (majorant * r.direction().magnitude());
+        let mut transmittance = 1.0;
+        let mut t = t_enter;
+        loop {
+            t += free_flight() * step;
+            if t >= t_exit {
+                return transmittance;
+            }
+            let density = self.field.density(&r.at(t)) * self.density_scale;
+            transmittance *= 1.0 - density / majorant;
+            if transmittance < 0.1 {
+                if random_double() < 0.5 {
+                    return 0.0;
+                }
+                transmittance *= 2.0;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Scatters uniformly in all directions.
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Arc<dyn Texture>) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn sample(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterSample> {
+        Some(ScatterSample {
+            scattered: Ray::new(hit_record.p, random_unit_vector(), ray_in.time()),
+            attenuation: self.albedo.value(hit_record.u, hit_record.v, &hit_record.p),
+            pdf: 1.0 / (4.0 * PI),
+            is_specular: false,
+        })
+    }
+
+    // Phase functions have no cosine term, so eval is the phase function itself
+    fn eval(
+        &self,
+        _ray_in: &Ray,
+        hit_record: &HitRecord,
+        _direction: &Vector3<f64>,
+    ) -> Vector3<f64> {
+        self.albedo.value(hit_record.u, hit_record.v, &hit_record.p) / (4.0 * PI)
+    }
+
+    fn pdf(&self, _ray_in: &Ray, _hit_record: &HitRecord, _direction: &Vector3<f64>) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+/// Henyey-Greenstein phase function. Positive `g` scatters forward (haze, clouds),
+/// negative `g` backwards, and 0 is isotropic.
+pub struct HenyeyGreenstein {
+    albedo: Arc<dyn Texture>,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Arc<dyn Texture>, g: f64) -> Self {
+        Self {
+            albedo,
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+
+    /// Density of scattering by an angle with cosine `cos_theta` off the ray's direction.
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g2 = self.g * self.g;
+        let denom = 1.0 + g2 - 2.0 * self.g * cos_theta;
+        (1.0 - g2) / (4.0 * PI * denom * denom.sqrt())
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn sample(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterSample> {
+        let g = self.g;
+        let xi = random_double();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        let local = Vector3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+        let direction = Onb::new(&ray_in.direction()).local(&local);
+
+        // Sampling is exact, so the phase function cancels against the pdf
+        Some(ScatterSample {
+            scattered: Ray::new(hit_record.p, direction, ray_in.time()),
+            attenuation: self.albedo.value(hit_record.u, hit_record.v, &hit_record.p),
+            pdf: self.phase(cos_theta),
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        self.albedo.value(hit_record.u, hit_record.v, &hit_record.p)
+            * self.pdf(ray_in, hit_record, direction)
+    }
+
+    fn pdf(&self, ray_in: &Ray, _hit_record: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        let cos_theta = ray_in.direction().normalize().dot(&direction.normalize());
+        self.phase(cos_theta)
+    }
+}
@@ -0,0 +1,222 @@
+//! GGX (Trowbridge-Reitz) microfacet distribution and Fresnel terms.
+//!
+//! Everything here works in a local shading frame with the macro surface normal along +z.
+
+use std::f64::consts::PI;
+
+use nalgebra::Vector3;
+
+use crate::utils::random_double;
+
+/// Isotropic GGX distribution with Smith's height-correlated masking-shadowing.
+#[derive(Clone, Copy, Debug)]
+pub struct Ggx {
+    alpha: f64,
+}
+
+impl Ggx {
+    /// Takes the perceptual roughness, squared into alpha. Very small values are clamped
+    /// to keep the distribution finite.
+    pub fn from_roughness(roughness: f64) -> Self {
+        Self {
+            alpha: (roughness.clamp(0.0, 1.0) * roughness.clamp(0.0, 1.0)).max(1e-3),
+        }
+    }
+
+    /// Density of microfacet normals `wh`.
+    pub fn d(&self, wh: &Vector3<f64>) -> f64 {
+        if wh.z <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let denom = wh.z * wh.z * (a2 - 1.0) + 1.0;
+        a2 / (PI * denom * denom)
+    }
+
+    fn lambda(&self, w: &Vector3<f64>) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0.0 {
+            return 0.0;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        ((1.0 + self.alpha * self.alpha * tan2).sqrt() - 1.0) / 2.0
+    }
+
+    /// Fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: &Vector3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Fraction of microfacets visible from both `wo` and `wi`.
+    pub fn g(&self, wo: &Vector3<f64>, wi: &Vector3<f64>) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Density with which `sample_visible_normal(wo)` returns `wh`.
+    pub fn visible_normal_pdf(&self, wo: &Vector3<f64>, wh: &Vector3<f64>) -> f64 {
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(wh).max(0.0) * self.d(wh) / wo.z
+    }
+
+    /// Samples a microfacet normal visible from `wo` (Heitz 2018), which avoids the
+    /// high-variance grazing samples of plain `d` sampling.
+    pub fn sample_visible_normal(&self, wo: &Vector3<f64>) -> Vector3<f64> {
+        // Stretch the view direction to the hemisphere configuration
+        let vh = Vector3::new(self.alpha * wo.x, self.alpha * wo.y, wo.z).normalize();
+
+        let len2 = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len2 > 0.0 {
+            Vector3::new(-vh.y, vh.x, 0.0) / len2.sqrt()
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        // Uniform disk sample, warped onto the visible half of the projected hemisphere
+        let r = random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+
+        // Unstretch back to the ellipsoid configuration
+        Vector3::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6)).normalize()
+    }
+
+    /// Samples a mirror reflection off a visible microfacet.
+    pub fn sample_reflection(&self, wo: &Vector3<f64>) -> Option<Vector3<f64>> {
+        let wi = reflect(wo, &self.sample_visible_normal(wo));
+        (wi.z > 0.0).then_some(wi)
+    }
+
+    /// For a reflection from `wo` into `wi`: the half vector, `D * G / (4 cos(wo))` (the BRDF
+    /// times cosine, short of the Fresnel term) and the density of `sample_reflection`.
+    pub fn reflection(
+        &self,
+        wo: &Vector3<f64>,
+        wi: &Vector3<f64>,
+    ) -> Option<(Vector3<f64>, f64, f64)> {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return None;
+        }
+        let wh = (wo + wi).normalize();
+        let value = self.d(&wh) * self.g(wo, wi) / (4.0 * wo.z);
+        let pdf = self.visible_normal_pdf(wo, &wh) / (4.0 * wo.dot(&wh));
+        Some((wh, value, pdf))
+    }
+
+    /// Samples reflection or refraction through a rough dielectric interface, chosen by
+    /// their Fresnel weights. `eta` is as in `refract`.
+    pub fn sample_dielectric(&self, wo: &Vector3<f64>, eta: f64) -> Option<Vector3<f64>> {
+        let wh = self.sample_visible_normal(wo);
+        if random_double() < fresnel_dielectric(wo.dot(&wh), eta) {
+            let wi = reflect(wo, &wh);
+            (wi.z > 0.0).then_some(wi)
+        } else {
+            let wi = refract(wo, &wh, eta)?;
+            (wi.z < 0.0).then_some(wi)
+        }
+    }
+
+    /// BSDF times cosine of a rough dielectric interface and the density of
+    /// `sample_dielectric`, for light arriving from `wi`.
+    pub fn dielectric(&self, wo: &Vector3<f64>, wi: &Vector3<f64>, eta: f64) -> (f64, f64) {
+        if wo.z <= 0.0 || wi.z == 0.0 {
+            return (0.0, 0.0);
+        }
+
+        if wi.z > 0.0 {
+            let Some((wh, value, pdf)) = self.reflection(wo, wi) else {
+                return (0.0, 0.0);
+            };
+            let fresnel = fresnel_dielectric(wo.dot(&wh), eta);
+            return (fresnel * value, fresnel * pdf);
+        }
+
+        // Half vector of the refraction, facing wo
+        let wh = wo + eta * wi;
+        if wh.magnitude_squared() < 1e-12 {
+            return (0.0, 0.0);
+        }
+        let wh = if wh.z < 0.0 { -wh } else { wh }.normalize();
+        let cos_o = wo.dot(&wh);
+        let cos_i = wi.dot(&wh);
+        if cos_o <= 0.0 || cos_i >= 0.0 {
+            return (0.0, 0.0);
+        }
+
+        let fresnel = fresnel_dielectric(cos_o, eta);
+        let denom = cos_o + eta * cos_i;
+        // The eta^2 of the Jacobian cancels in the value against the radiance scaling
+        // by 1 / eta^2 when crossing into the denser medium
+        let value = (1.0 - fresnel) * self.d(&wh) * self.g(wo, wi) * cos_o * -cos_i
+            / (wo.z * denom * denom);
+        let pdf = (1.0 - fresnel) * self.visible_normal_pdf(wo, &wh) * eta * eta * -cos_i
+            / (denom * denom);
+        (value, pdf)
+    }
+}
+
+fn reflect(w: &Vector3<f64>, n: &Vector3<f64>) -> Vector3<f64> {
+    -w + 2.0 * w.dot(n) * n
+}
+
+/// Refracts `w` (pointing away from the surface) through the facet `n` on its side,
+/// where `eta` is the ratio of the far side's IOR over `w`'s. `None` on total internal
+/// reflection.
+fn refract(w: &Vector3<f64>, n: &Vector3<f64>, eta: f64) -> Option<Vector3<f64>> {
+    let cos_i = w.dot(n);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-w / eta + (cos_i / eta - cos_t) * n)
+}
+
+/// Unpolarized Fresnel reflectance of a dielectric interface, `eta` as in `refract`.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (rs * rs + rp * rp) / 2.0
+}
+
+/// Schlick's approximation of the Fresnel reflectance for a normal incidence value `f0`.
+pub fn fresnel_schlick(f0: &Vector3<f64>, cos_i: f64) -> Vector3<f64> {
+    let weight = (1.0 - cos_i.clamp(0.0, 1.0)).powi(5);
+    f0 + (Vector3::repeat(1.0) - f0) * weight
+}
+
+/// Fresnel reflectance of a conductor with complex IOR `eta + ik`, per color channel.
+pub fn fresnel_conductor(cos_i: f64, eta: &Vector3<f64>, k: &Vector3<f64>) -> Vector3<f64> {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let cos2 = cos_i * cos_i;
+    let sin2 = 1.0 - cos2;
+
+    Vector3::from_fn(|i, _| {
+        let eta2 = eta[i] * eta[i];
+        let k2 = k[i] * k[i];
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+        let t1 = a2_plus_b2 + cos2;
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        (rs + rp) / 2.0
+    })
+}
@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use nalgebra::Vector3;
+
+use crate::{
+    hittable::{Hittable, HittableList},
+    material::{Dielectric, DiffuseLight, Lambertian, Material, Metal},
+    texture::ImageTexture,
+    triangle::TriangleMesh,
+};
+
+/// Subset of an MTL entry that maps onto our materials.
+#[derive(Clone, Debug)]
+struct MtlMaterial {
+    diffuse: Vector3<f64>,  // Kd
+    emission: Vector3<f64>, // Ke
+    specular: Vector3<f64>, // Ks
+    shininess: f64,         // Ns
+    ior: f64,               // Ni
+    dissolve: f64,          // d (or 1 - Tr)
+    illum: Option<u32>,
+    diffuse_map: Option<PathBuf>, // map_Kd, resolved against the MTL's directory
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            diffuse: Vector3::new(0.8, 0.8, 0.8),
+            emission: Vector3::new(0.0, 0.0, 0.0),
+            specular: Vector3::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            ior: 1.5,
+            dissolve: 1.0,
+            illum: None,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn is_emissive(&self) -> bool {
+        self.emission.max() > 0.0
+    }
+
+    fn to_material(&self) -> Result<Arc<dyn Material>, String> {
+        if self.is_emissive() {
+            return Ok(Arc::new(DiffuseLight::from_color(self.emission)));
+        }
+
+        let transparent = self.dissolve < 1.0 || matches!(self.illum, Some(4 | 6 | 7 | 9));
+        if transparent {
+            return Ok(Arc::new(Dielectric::new(self.ior)));
+        }
+
+        // Treat surfaces whose specular lobe dominates the diffuse one as metals
+        if self.specular.max() > 0.0 && self.specular.max() >= self.diffuse.max() {
+            // Map the Blinn-Phong exponent onto a fuzz amount
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            return Ok(Arc::new(Metal::from_color(self.specular, fuzz)));
+        }
+
+        // Exporters usually leave Kd at some default next to a map, so the map wins
+        if let Some(path) = &self.diffuse_map {
+            let texture = ImageTexture::load(path)?;
+            return Ok(Arc::new(Lambertian::new(Arc::new(texture))));
+        }
+
+        Ok(Arc::new(Lambertian::from_color(self.diffuse)))
+    }
+}
+
+/// Unified vertex buffers for one group/material batch of faces.
+#[derive(Default)]
+struct MeshBuilder {
+    positions: Vec<Vector3<f64>>,
+    normals: Vec<Option<Vector3<f64>>>,
+    uvs: Vec<Option<(f64, f64)>>,
+    indices: Vec<[usize; 3]>,
+    // (position, uv, normal) index triple -> unified vertex
+    vertex_lookup: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, obj: &ObjData, key: (usize, Option<usize>, Option<usize>)) -> usize {
+        if let Some(&index) = self.vertex_lookup.get(&key) {
+            return index;
+        }
+
+        let (p, t, n) = key;
+        let index = self.positions.len();
+        self.positions.push(obj.positions[p]);
+        self.uvs.push(t.map(|t| obj.uvs[t]));
+        self.normals.push(n.map(|n| obj.normals[n]));
+        self.vertex_lookup.insert(key, index);
+        index
+    }
+
+    fn build(self, material: Arc<dyn Material>) -> Result<TriangleMesh, String> {
+        // Only keep attributes that every vertex provides
+        let normals = self.normals.into_iter().collect::<Option<Vec<_>>>();
+        let uvs = self.uvs.into_iter().collect::<Option<Vec<_>>>();
+        TriangleMesh::new(self.positions, self.indices, normals, uvs, material)
+    }
+}
+
+#[derive(Default)]
+struct ObjData {
+    positions: Vec<Vector3<f64>>,
+    normals: Vec<Vector3<f64>>,
+    uvs: Vec<(f64, f64)>,
+}
+
+fn parse_floats<const N: usize>(
+    args: &[&str],
+    line_number: usize,
+    path: &Path,
+) -> Result<[f64; N], String> {
+    let mut values = [0.0; N];
+    if args.len() < N {
+        return Err(format!(
+            "{}:{}: expected {} numbers, found {}",
+            path.display(),
+            line_number,
+            N,
+            args.len()
+        ));
+    }
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg.parse().map_err(|_| {
+            format!(
+                "{}:{}: invalid number '{}'",
+                path.display(),
+                line_number,
+                arg
+            )
+        })?;
+    }
+    Ok(values)
+}
+
+/// Resolves a 1-based (or negative, relative) OBJ index against `len` elements.
+fn resolve_index(raw: &str, len: usize, line_number: usize, path: &Path) -> Result<usize, String> {
+    let index: i64 = raw.parse().map_err(|_| {
+        format!(
+            "{}:{}: invalid index '{}'",
+            path.display(),
+            line_number,
+            raw
+        )
+    })?;
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(format!(
+            "{}:{}: index {} out of range",
+            path.display(),
+            line_number,
+            index
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, MtlMaterial>, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&keyword, args)) = tokens.split_first() else {
+            continue;
+        };
+        if keyword.starts_with('#') {
+            continue;
+        }
+
+        if keyword == "newmtl" {
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((args.join(" "), MtlMaterial::default()));
+            continue;
+        }
+
+        let Some((_, material)) = current.as_mut() else {
+            continue;
+        };
+        let vector = || parse_floats::<3>(args, line_number, path).map(Vector3::from);
+        let scalar = || parse_floats::<1>(args, line_number, path).map(|[x]| x);
+        match keyword {
+            "Kd" => material.diffuse = vector()?,
+            "Ks" => material.specular = vector()?,
+            "Ke" => material.emission = vector()?,
+            "Ns" => material.shininess = scalar()?,
+            "Ni" => material.ior = scalar()?,
+            "d" => material.dissolve = scalar()?,
+            "Tr" => material.dissolve = 1.0 - scalar()?,
+            "illum" => material.illum = Some(scalar()? as u32),
+            "map_Kd" => {
+                // Options such as `-s` precede the file name, and aren't supported
+                let file = args.last().ok_or_else(|| {
+                    format!("{}:{}: missing texture file", path.display(), line_number)
+                })?;
+                material.diffuse_map = Some(base_dir.join(file));
+            }
+            _ => {} // Unsupported statements are ignored
+        }
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+
+    Ok(materials)
+}
+
+pub struct ObjModel {
+    pub meshes: HittableList,
+    // Faces whose MTL material emits light, for explicit light sampling
+    pub lights: Vec<Arc<dyn Hittable>>,
+}
+
+/// Loads a Wavefront OBJ file into one `TriangleMesh` per group and material.
+///
+/// Faces without a `usemtl` statement, or referring to an unknown material, use
+/// `default_material`. Polygons are triangulated as fans.
+pub fn load_obj(path: &Path, default_material: Arc<dyn Material>) -> Result<ObjModel, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut obj = ObjData::default();
+    let mut mtl_materials: HashMap<String, MtlMaterial> = HashMap::new();
+    let mut resolved_materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+    // Batches keyed by (group, material name), kept in file order
+    let mut batches: Vec<((String, Option<String>), MeshBuilder)> = Vec::new();
+    let mut batch_lookup: HashMap<(String, Option<String>), usize> = HashMap::new();
+    let mut group = String::new();
+    let mut material_name: Option<String> = None;
+
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&keyword, args)) = tokens.split_first() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => obj
+                .positions
+                .push(Vector3::from(parse_floats::<3>(args, line_number, path)?)),
+            "vn" => obj
+                .normals
+                .push(Vector3::from(parse_floats::<3>(args, line_number, path)?)),
+            "vt" => {
+                // The w component, if any, is ignored and v defaults to 0
+                let u = parse_floats::<1>(args, line_number, path)?[0];
+                let v = match args.get(1) {
+                    Some(_) => parse_floats::<1>(&args[1..], line_number, path)?[0],
+                    None => 0.0,
+                };
+                obj.uvs.push((u, v));
+            }
+            "g" | "o" => group = args.join(" "),
+            "usemtl" => material_name = Some(args.join(" ")),
+            "mtllib" => {
+                for file in args {
+                    mtl_materials.extend(load_mtl(&base_dir.join(file))?);
+                }
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(format!(
+                        "{}:{}: face needs at least 3 vertices",
+                        path.display(),
+                        line_number
+                    ));
+                }
+
+                let mut corners = Vec::with_capacity(args.len());
+                for corner in args {
+                    let mut parts = corner.split('/');
+                    let p = resolve_index(
+                        parts.next().unwrap_or_default(),
+                        obj.positions.len(),
+                        line_number,
+                        path,
+                    )?;
+                    let t = match parts.next() {
+                        Some(raw) if !raw.is_empty() => {
+                            Some(resolve_index(raw, obj.uvs.len(), line_number, path)?)
+                        }
+                        _ => None,
+                    };
+                    let n = match parts.next() {
+                        Some(raw) if !raw.is_empty() => {
+                            Some(resolve_index(raw, obj.normals.len(), line_number, path)?)
+                        }
+                        _ => None,
+                    };
+                    corners.push((p, t, n));
+                }
+
+                let key = (group.clone(), material_name.clone());
+                let index = *batch_lookup.entry(key.clone()).or_insert_with(|| {
+                    batches.push((key, MeshBuilder::default()));
+                    batches.len() - 1
+                });
+                let batch = &mut batches[index].1;
+
+                let indices: Vec<usize> = corners
+                    .into_iter()
+                    .map(|corner| batch.vertex(&obj, corner))
+                    .collect();
+                for k in 1..indices.len() - 1 {
+                    batch.indices.push([indices[0], indices[k], indices[k + 1]]);
+                }
+            }
+            _ => {} // Comments, smoothing groups and other statements
+        }
+    }
+
+    let mut model = ObjModel {
+        meshes: HittableList::new(),
+        lights: Vec::new(),
+    };
+    for ((_, name), batch) in batches {
+        let (material, emissive) = match name.filter(|name| mtl_materials.contains_key(name)) {
+            Some(name) => {
+                let mtl = &mtl_materials[&name];
+                let material = match resolved_materials.get(&name) {
+                    Some(material) => Arc::clone(material),
+                    None => {
+                        let material = mtl
+                            .to_material()
+                            .map_err(|e| format!("material '{}': {}", name, e))?;
+                        resolved_materials.insert(name, Arc::clone(&material));
+                        material
+                    }
+                };
+                (material, mtl.is_emissive())
+            }
+            None => (Arc::clone(&default_material), false),
+        };
+
+        let mesh = batch
+            .build(material)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        if emissive {
+            model.lights.extend(mesh.faces().iter().cloned());
+        }
+        model.meshes.add(Arc::new(mesh));
+    }
+
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(raw: &str, len: usize) -> Result<usize, String> {
+        resolve_index(raw, len, 1, Path::new("test.obj"))
+    }
+
+    #[test]
+    fn positive_indices_are_one_based() {
+        assert_eq!(resolve("1", 3), Ok(0));
+        assert_eq!(resolve("3", 3), Ok(2));
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_end() {
+        assert_eq!(resolve("-1", 3), Ok(2));
+        assert_eq!(resolve("-3", 3), Ok(0));
+    }
+
+    #[test]
+    fn index_zero_is_an_error() {
+        assert!(resolve("0", 3).is_err());
+    }
+
+    #[test]
+    fn out_of_range_indices_are_errors() {
+        assert!(resolve("4", 3).is_err());
+        assert!(resolve("-4", 3).is_err());
+        assert!(resolve("1", 0).is_err());
+        assert!(resolve("-1", 0).is_err());
+        assert!(resolve("99999999999999999999", 3).is_err());
+    }
+
+    #[test]
+    fn malformed_indices_are_errors() {
+        assert!(resolve("", 3).is_err());
+        assert!(resolve("x", 3).is_err());
+        assert!(resolve("1.5", 3).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use image::{ImageBuffer, ImageFormat, Rgb};
+use nalgebra::Vector3;
+
+use crate::{tonemap::ToneMapping, utils};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    /// 32-bit float OpenEXR
+    Exr,
+    /// Radiance RGBE
+    Hdr,
+    /// Portable float map
+    Pfm,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "exr" => Some(Self::Exr),
+            "hdr" => Some(Self::Hdr),
+            "pfm" => Some(Self::Pfm),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Exr => "exr",
+            Self::Hdr => "hdr",
+            Self::Pfm => "pfm",
+        }
+    }
+}
+
+/// Writes a linear radiance framebuffer, stored row by row from the top, to `path`.
+/// Low dynamic range formats are tone mapped and sRGB encoded, HDR ones keep the raw values.
+pub fn save_image(
+    pixels: &[Vector3<f64>],
+    width: u32,
+    height: u32,
+    path: &Path,
+    format: OutputFormat,
+    tone_mapping: &ToneMapping,
+) -> Result<(), String> {
+    let ldr = |image_format| save_ldr(pixels, width, height, path, image_format, tone_mapping);
+    let result = match format {
+        OutputFormat::Png => ldr(ImageFormat::Png),
+        OutputFormat::Jpeg => ldr(ImageFormat::Jpeg),
+        OutputFormat::Exr => save_float(pixels, width, height, path, ImageFormat::OpenExr),
+        OutputFormat::Hdr => save_float(pixels, width, height, path, ImageFormat::Hdr),
+        OutputFormat::Pfm => save_pfm(pixels, width, height, path).map_err(|e| e.to_string()),
+    };
+    result.map_err(|e| format!("Failed to write '{}': {}", path.display(), e))
+}
+
+fn save_ldr(
+    pixels: &[Vector3<f64>],
+    width: u32,
+    height: u32,
+    path: &Path,
+    format: ImageFormat,
+    tone_mapping: &ToneMapping,
+) -> Result<(), String> {
+    let mut img = ImageBuffer::new(width, height);
+
+    for (i, pixel) in pixels.iter().enumerate() {
+        let x = i as u32 % width;
+        let y = i as u32 / width;
+
+        let display = tone_mapping.apply(pixel);
+        let [r, g, b] = [display.x, display.y, display.z]
+            .map(|c| (255.0 * utils::linear_to_srgb(c)).round() as u8);
+
+        img.put_pixel(x, y, Rgb([r, g, b]));
+    }
+
+    img.save_with_format(path, format)
+        .map_err(|e| e.to_string())
+}
+
+fn save_float(
+    pixels: &[Vector3<f64>],
+    width: u32,
+    height: u32,
+    path: &Path,
+    format: ImageFormat,
+) -> Result<(), String> {
+    let data: Vec<f32> = pixels
+        .iter()
+        .flat_map(|pixel| [pixel.x as f32, pixel.y as f32, pixel.z as f32])
+        .collect();
+    let img: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::from_raw(width, height, data)
+        .ok_or_else(|| "framebuffer size doesn't match the image dimensions".to_string())?;
+
+    img.save_with_format(path, format)
+        .map_err(|e| e.to_string())
+}
+
+fn save_pfm(pixels: &[Vector3<f64>], width: u32, height: u32, path: &Path) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    // A negative scale marks little-endian data; rows are stored bottom to top
+    write!(writer, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in pixels.chunks(width as usize).rev() {
+        for pixel in row {
+            for component in [pixel.x, pixel.y, pixel.z] {
+                writer.write_all(&(component as f32).to_le_bytes())?;
+            }
+        }
+    }
+
+    writer.flush()
+}
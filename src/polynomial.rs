@@ -0,0 +1,166 @@
+/// Real roots of `a t² + 2 half_b t + c`, in increasing order. Degenerates to the linear
+/// equation when `a` vanishes, e.g. for rays parallel to a cylinder's axis.
+pub fn solve_quadratic(a: f64, half_b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        if half_b.abs() < 1e-12 {
+            return Vec::new();
+        }
+        return vec![-c / (2.0 * half_b)];
+    }
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+    // Avoids cancellation between -half_b and the square root
+    let q = -(half_b + half_b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return vec![0.0];
+    }
+    let (t0, t1) = (q / a, c / q);
+    vec![t0.min(t1), t0.max(t1)]
+}
+
+/// Iterations of bisection per root, enough to exhaust f64 precision on any bracket.
+const BISECTION_STEPS: usize = 100;
+
+/// Real roots of the polynomial with `coefficients` (highest degree first) within
+/// `[lo, hi]`, in increasing order.
+///
+/// The roots of the derivative split the interval into monotonic pieces, each holding at
+/// most one root that bisection then finds. Slower than closed forms, but it stays accurate
+/// for the quartics of tori where Ferrari's method loses most of its digits.
+pub fn real_roots(coefficients: &[f64], lo: f64, hi: f64) -> Vec<f64> {
+    let degree = coefficients.len().saturating_sub(1);
+    match degree {
+        0 => return Vec::new(),
+        1 => {
+            let [a, b] = [coefficients[0], coefficients[1]];
+            if a == 0.0 {
+                return Vec::new();
+            }
+            let t = -b / a;
+            return if (lo..=hi).contains(&t) {
+                vec![t]
+            } else {
+                Vec::new()
+            };
+        }
+        _ => {}
+    }
+
+    let derivative: Vec<f64> = coefficients[..degree]
+        .iter()
+        .enumerate()
+        .map(|(i, c)| c * (degree - i) as f64)
+        .collect();
+
+    let mut bounds = vec![lo];
+    bounds.extend(real_roots(&derivative, lo, hi));
+    bounds.push(hi);
+
+    let evaluate = |t: f64| coefficients.iter().fold(0.0, |acc, c| acc * t + c);
+    let mut roots = Vec::new();
+    for window in bounds.windows(2) {
+        let (mut a, mut b) = (window[0], window[1]);
+        let (fa, fb) = (evaluate(a), evaluate(b));
+        if fa == 0.0 {
+            roots.push(a);
+            continue;
+        }
+        // Checked apart from the signs, as the sign of 0 would match a positive `fa`
+        if fb == 0.0 {
+            roots.push(b);
+            continue;
+        }
+        if fa.signum() == fb.signum() {
+            continue;
+        }
+        for _ in 0..BISECTION_STEPS {
+            let mid = 0.5 * (a + b);
+            if mid <= a || mid >= b {
+                break;
+            }
+            if evaluate(mid).signum() == fa.signum() {
+                a = mid;
+            } else {
+                b = mid;
+            }
+        }
+        roots.push(0.5 * (a + b));
+    }
+    // A root on a shared bound shows up in both windows
+    roots.dedup();
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(actual: &[f64], expected: &[f64]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn quadratic_roots_are_sorted() {
+        // 2t² - 2t - 4 = 2(t + 1)(t - 2)
+        assert_roots(&solve_quadratic(2.0, -1.0, -4.0), &[-1.0, 2.0]);
+        assert_roots(&solve_quadratic(1.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn quadratic_degenerates_to_linear() {
+        assert_roots(&solve_quadratic(0.0, 1.0, -4.0), &[2.0]);
+        assert_roots(&solve_quadratic(0.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn double_root() {
+        // (t - 1)²
+        assert_roots(&real_roots(&[1.0, -2.0, 1.0], -10.0, 10.0), &[1.0]);
+        assert_roots(&solve_quadratic(1.0, -1.0, 1.0), &[1.0, 1.0]);
+    }
+
+    #[test]
+    fn roots_on_the_bounds() {
+        // ±(t - 1)(t - 2), so that both signs of the polynomial meet each bound
+        for sign in [1.0, -1.0] {
+            let coefficients = [sign, -3.0 * sign, 2.0 * sign];
+            assert_roots(&real_roots(&coefficients, 1.0, 2.0), &[1.0, 2.0]);
+            assert_roots(&real_roots(&coefficients, 0.0, 2.0), &[1.0, 2.0]);
+            assert_roots(&real_roots(&coefficients, 1.0, 1.5), &[1.0]);
+        }
+    }
+
+    #[test]
+    fn quartic_with_four_real_roots() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        let coefficients = [1.0, -10.0, 35.0, -50.0, 24.0];
+        assert_roots(
+            &real_roots(&coefficients, -100.0, 100.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        assert_roots(&real_roots(&coefficients, 1.5, 3.5), &[2.0, 3.0]);
+    }
+
+    #[test]
+    fn degenerate_leading_coefficient() {
+        // 0t³ + t² - 3t + 2 = (t - 1)(t - 2)
+        assert_roots(
+            &real_roots(&[0.0, 1.0, -3.0, 2.0], -10.0, 10.0),
+            &[1.0, 2.0],
+        );
+        assert_roots(&real_roots(&[0.0, 2.0, -1.0], -10.0, 10.0), &[0.5]);
+        assert_roots(&real_roots(&[0.0, 5.0], -10.0, 10.0), &[]);
+    }
+}
@@ -0,0 +1,319 @@
+//! Principled (Disney-style) material: diffuse, sheen, specular, clearcoat and transmission
+//! lobes driven by one set of artist-friendly parameters.
+
+use std::{f64::consts::PI, sync::Arc};
+
+use nalgebra::Vector3;
+
+use crate::{
+    hittable::HitRecord,
+    material::{Material, ScatterSample},
+    microfacet::{fresnel_schlick, Ggx},
+    ray::Ray,
+    sampling::{random_cosine_direction, Onb},
+    texture::{SolidColor, Texture},
+    utils::random_double,
+};
+
+/// Every parameter except `ior` is a texture; scalar ones read its mean channel.
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: Arc<dyn Texture>,
+    roughness: Arc<dyn Texture>,
+    specular: Arc<dyn Texture>,
+    specular_tint: Arc<dyn Texture>,
+    sheen: Arc<dyn Texture>,
+    sheen_tint: Arc<dyn Texture>,
+    clearcoat: Arc<dyn Texture>,
+    clearcoat_roughness: Arc<dyn Texture>,
+    transmission: Arc<dyn Texture>,
+    ior: f64,
+}
+
+fn constant(value: f64) -> Arc<dyn Texture> {
+    Arc::new(SolidColor::new(Vector3::repeat(value)))
+}
+
+impl Principled {
+    /// A plastic-like dielectric; the other parameters are set with the `with_*` methods.
+    pub fn new(base_color: Arc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.0),
+            sheen: constant(0.0),
+            sheen_tint: constant(0.5),
+            clearcoat: constant(0.0),
+            clearcoat_roughness: constant(0.03),
+            transmission: constant(0.0),
+            ior: 1.5,
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: Arc<dyn Texture>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: Arc<dyn Texture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    /// Dielectric reflectivity, where the default 0.5 is an F0 of 4%.
+    pub fn with_specular(mut self, specular: Arc<dyn Texture>) -> Self {
+        self.specular = specular;
+        self
+    }
+
+    pub fn with_specular_tint(mut self, specular_tint: Arc<dyn Texture>) -> Self {
+        self.specular_tint = specular_tint;
+        self
+    }
+
+    /// Grazing retro-reflection for cloth-like surfaces.
+    pub fn with_sheen(mut self, sheen: Arc<dyn Texture>) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    pub fn with_sheen_tint(mut self, sheen_tint: Arc<dyn Texture>) -> Self {
+        self.sheen_tint = sheen_tint;
+        self
+    }
+
+    /// Strength of a clear varnish layer on top of everything else.
+    pub fn with_clearcoat(mut self, clearcoat: Arc<dyn Texture>) -> Self {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    pub fn with_clearcoat_roughness(mut self, clearcoat_roughness: Arc<dyn Texture>) -> Self {
+        self.clearcoat_roughness = clearcoat_roughness;
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: Arc<dyn Texture>) -> Self {
+        self.transmission = transmission;
+        self
+    }
+
+    pub fn with_ior(mut self, ior: f64) -> Self {
+        self.ior = ior;
+        self
+    }
+
+    fn lobes(&self, ray_in: &Ray, hit_record: &HitRecord) -> Lobes {
+        let (u, v, p) = (hit_record.u, hit_record.v, &hit_record.p);
+        let scalar = |texture: &Arc<dyn Texture>| texture.value(u, v, p).mean().clamp(0.0, 1.0);
+
+        let frame = hit_record.shading_frame();
+        let wo = frame.to_local(&-ray_in.direction().normalize());
+
+        let base = self.base_color.value(u, v, p);
+        let luminance = base.dot(&Vector3::new(0.2126, 0.7152, 0.0722));
+        let tint = if luminance > 0.0 {
+            base / luminance
+        } else {
+            Vector3::repeat(1.0)
+        };
+        let white = Vector3::repeat(1.0);
+
+        let mut metallic = scalar(&self.metallic);
+        let mut transmission = scalar(&self.transmission);
+        let mut clearcoat = scalar(&self.clearcoat);
+        let mut transmission_tint = base;
+        // Inside a transmissive object only the interface back out matters, and the base
+        // color was already applied on the way in
+        if !hit_record.front_face && transmission > 0.0 {
+            metallic = 0.0;
+            transmission = 1.0;
+            clearcoat = 0.0;
+            transmission_tint = white;
+        }
+
+        let specular_f0 =
+            0.08 * scalar(&self.specular) * white.lerp(&tint, scalar(&self.specular_tint));
+        let sheen_color = scalar(&self.sheen) * white.lerp(&tint, scalar(&self.sheen_tint));
+
+        let mut lobes = Lobes {
+            frame,
+            wo,
+            base,
+            metallic,
+            dielectric: (1.0 - metallic) * (1.0 - transmission),
+            transmission: (1.0 - metallic) * transmission,
+            transmission_tint,
+            specular_f0,
+            sheen_color,
+            clearcoat,
+            coat_transmittance: 1.0 - clearcoat * coat_fresnel(wo.z),
+            eta: if hit_record.front_face {
+                self.ior
+            } else {
+                1.0 / self.ior
+            },
+            specular: Ggx::from_roughness(scalar(&self.roughness)),
+            coat: Ggx::from_roughness(scalar(&self.clearcoat_roughness)),
+            probabilities: [0.0; 4],
+        };
+        lobes.probabilities = lobes.selection_probabilities();
+        lobes
+    }
+}
+
+fn coat_fresnel(cos: f64) -> f64 {
+    fresnel_schlick(&Vector3::repeat(0.04), cos).x
+}
+
+const DIFFUSE: usize = 0;
+const SPECULAR: usize = 1;
+const CLEARCOAT: usize = 2;
+const TRANSMISSION: usize = 3;
+
+/// Parameters evaluated at one hit, in the local shading frame.
+struct Lobes {
+    frame: Onb,
+    wo: Vector3<f64>,
+    base: Vector3<f64>,
+    metallic: f64,
+    // Weights of the opaque dielectric and transmissive parts of the base layer
+    dielectric: f64,
+    transmission: f64,
+    transmission_tint: Vector3<f64>,
+    specular_f0: Vector3<f64>,
+    sheen_color: Vector3<f64>,
+    clearcoat: f64,
+    // Fraction of light making it through the clearcoat to the base layer
+    coat_transmittance: f64,
+    eta: f64,
+    specular: Ggx,
+    coat: Ggx,
+    probabilities: [f64; 4],
+}
+
+impl Lobes {
+    /// Fresnel reflectance of the base layer's specular lobe.
+    fn specular_fresnel(&self, cos: f64) -> Vector3<f64> {
+        self.coat_transmittance
+            * (self.metallic * fresnel_schlick(&self.base, cos)
+                + self.dielectric * fresnel_schlick(&self.specular_f0, cos))
+    }
+
+    /// Share of light below the dielectric specular lobe that reaches the diffuse one.
+    fn diffuse_weight(&self) -> f64 {
+        let reflected = fresnel_schlick(&self.specular_f0, self.wo.z).mean();
+        self.coat_transmittance * self.dielectric * (1.0 - reflected)
+    }
+
+    /// Picks lobes in proportion to their estimated albedo as seen from `wo`.
+    fn selection_probabilities(&self) -> [f64; 4] {
+        let mut weights = [0.0; 4];
+        weights[DIFFUSE] = self.diffuse_weight() * (self.base + self.sheen_color).mean();
+        weights[SPECULAR] = self.specular_fresnel(self.wo.z).mean();
+        weights[CLEARCOAT] = self.clearcoat * coat_fresnel(self.wo.z);
+        weights[TRANSMISSION] = self.coat_transmittance * self.transmission;
+
+        let total: f64 = weights.iter().sum();
+        if total > 0.0 {
+            weights.map(|w| w / total)
+        } else {
+            weights
+        }
+    }
+
+    fn sample(&self) -> Option<Vector3<f64>> {
+        if self.probabilities.iter().all(|&p| p <= 0.0) {
+            return None;
+        }
+        let mut r = random_double();
+        let mut lobe = self.probabilities.iter().rposition(|&p| p > 0.0)?;
+        for (i, &p) in self.probabilities.iter().enumerate() {
+            if r < p {
+                lobe = i;
+                break;
+            }
+            r -= p;
+        }
+
+        match lobe {
+            DIFFUSE => Some(random_cosine_direction()),
+            SPECULAR => self.specular.sample_reflection(&self.wo),
+            CLEARCOAT => self.coat.sample_reflection(&self.wo),
+            _ => self.specular.sample_dielectric(&self.wo, self.eta),
+        }
+    }
+
+    /// BSDF times cosine summed over all lobes, and the combined density of `sample`.
+    fn eval(&self, wi: &Vector3<f64>) -> (Vector3<f64>, f64) {
+        let wo = &self.wo;
+        let mut value = Vector3::new(0.0, 0.0, 0.0);
+        let mut pdf = 0.0;
+        if wo.z <= 0.0 {
+            return (value, pdf);
+        }
+
+        if wi.z > 0.0 {
+            let cos_d = wi.dot(&(wo + wi).normalize());
+            let sheen = self.sheen_color * (1.0 - cos_d).powi(5);
+            value += self.diffuse_weight() * (self.base + sheen) * (wi.z / PI);
+            pdf += self.probabilities[DIFFUSE] * wi.z / PI;
+
+            if let Some((wh, f, p)) = self.specular.reflection(wo, wi) {
+                value += self.specular_fresnel(wo.dot(&wh)) * f;
+                pdf += self.probabilities[SPECULAR] * p;
+            }
+            if let Some((wh, f, p)) = self.coat.reflection(wo, wi) {
+                value += Vector3::repeat(self.clearcoat * coat_fresnel(wo.dot(&wh)) * f);
+                pdf += self.probabilities[CLEARCOAT] * p;
+            }
+        }
+
+        if self.transmission > 0.0 {
+            let (f, p) = self.specular.dielectric(wo, wi, self.eta);
+            let tint = if wi.z < 0.0 {
+                self.transmission_tint
+            } else {
+                Vector3::repeat(1.0)
+            };
+            value += self.coat_transmittance * self.transmission * f * tint;
+            pdf += self.probabilities[TRANSMISSION] * p;
+        }
+
+        (value, pdf)
+    }
+}
+
+impl Material for Principled {
+    fn sample(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<ScatterSample> {
+        let lobes = self.lobes(ray_in, hit_record);
+        if lobes.wo.z <= 0.0 {
+            return None;
+        }
+
+        // Weighting by the density of every lobe makes this a one-sample MIS estimate
+        let wi = lobes.sample()?;
+        let (value, pdf) = lobes.eval(&wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(ScatterSample {
+            scattered: Ray::new(hit_record.p, lobes.frame.local(&wi), ray_in.time()),
+            attenuation: value / pdf,
+            pdf,
+            is_specular: false,
+        })
+    }
+
+    fn eval(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector3<f64>) -> Vector3<f64> {
+        let lobes = self.lobes(ray_in, hit_record);
+        lobes.eval(&lobes.frame.to_local(&direction.normalize())).0
+    }
+
+    fn pdf(&self, ray_in: &Ray, hit_record: &HitRecord, direction: &Vector3<f64>) -> f64 {
+        let lobes = self.lobes(ray_in, hit_record);
+        lobes.eval(&lobes.frame.to_local(&direction.normalize())).1
+    }
+}
@@ -0,0 +1,329 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use nalgebra::Vector3;
+
+use crate::{
+    aabb::AxisAlignedBoundingBox,
+    hittable::{HitRecord, Hittable, HittableList},
+    material::Material,
+    ray::Ray,
+    sampling::Onb,
+    utils::random_double,
+};
+
+const BBOX_PADDING: f64 = 1e-4;
+
+/// Distance along `r` to the plane through `point` with unit `normal`.
+fn intersect_plane(
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+    point: &Vector3<f64>,
+    normal: &Vector3<f64>,
+) -> Option<f64> {
+    let denominator = normal.dot(&r.direction());
+    // Ray is parallel to the plane
+    if denominator.abs() < 1e-12 {
+        return None;
+    }
+
+    let t = normal.dot(&(point - r.origin())) / denominator;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    Some(t)
+}
+
+/// Solid angle density of sampling a uniform point on a flat shape of `area`, whose surface
+/// `direction` reaches after `t`.
+fn area_pdf_value(t: f64, direction: &Vector3<f64>, normal: &Vector3<f64>, area: f64) -> f64 {
+    let distance_squared = t * t * direction.magnitude_squared();
+    let cosine = (direction.dot(normal) / direction.magnitude()).abs();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+    distance_squared / (cosine * area)
+}
+
+fn hit_record(
+    r: &Ray,
+    t: f64,
+    (u, v): (f64, f64),
+    dpdu: Vector3<f64>,
+    dpdv: Vector3<f64>,
+    outward_normal: Vector3<f64>,
+    material: &Arc<dyn Material>,
+) -> HitRecord {
+    let mut hit_record = HitRecord {
+        p: r.at(t),
+        normal: Vector3::new(0.0, 0.0, 0.0),
+        t,
+        u,
+        v,
+        dpdu,
+        dpdv,
+        front_face: false,
+        material: Arc::clone(material),
+    };
+    hit_record.set_face_normal(r, outward_normal);
+    hit_record
+}
+
+/// Parallelogram spanned by the edges `u` and `v` from the corner `q`, facing `u × v`.
+/// Texture coordinates run from 0 to 1 along each edge.
+pub struct Quad {
+    q: Vector3<f64>,
+    u: Vector3<f64>,
+    v: Vector3<f64>,
+    normal: Vector3<f64>,
+    // Maps a point on the plane to its coordinates along the edges
+    w: Vector3<f64>,
+    area: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(
+        q: Vector3<f64>,
+        u: Vector3<f64>,
+        v: Vector3<f64>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let n = u.cross(&v);
+        Self {
+            q,
+            u,
+            v,
+            normal: n.normalize(),
+            w: n / n.dot(&n),
+            area: n.magnitude(),
+            material,
+        }
+    }
+
+    /// Edge coordinates of a hit, `None` outside the parallelogram.
+    fn coordinates(&self, p: &Vector3<f64>) -> Option<(f64, f64)> {
+        let planar = p - self.q;
+        let alpha = self.w.dot(&planar.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some((alpha, beta))
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = intersect_plane(r, t_min, t_max, &self.q, &self.normal)?;
+        let uv = self.coordinates(&r.at(t))?;
+        Some(hit_record(
+            r,
+            t,
+            uv,
+            self.u,
+            self.v,
+            self.normal,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        let diagonal = AxisAlignedBoundingBox::new(self.q, self.q + self.u + self.v);
+        let other = AxisAlignedBoundingBox::new(self.q + self.u, self.q + self.v);
+        Some(AxisAlignedBoundingBox::surrounding(&diagonal, &other).padded(BBOX_PADDING))
+    }
+
+    fn pdf_value(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> f64 {
+        match self.hit(&Ray::new(*origin, *direction, 0.0), 0.001, f64::INFINITY) {
+            Some(hit) => area_pdf_value(hit.t, direction, &self.normal, self.area),
+            None => 0.0,
+        }
+    }
+
+    fn random_direction(&self, origin: &Vector3<f64>) -> Vector3<f64> {
+        let point = self.q + random_double() * self.u + random_double() * self.v;
+        point - origin
+    }
+}
+
+/// Infinite plane through `point`. Texture coordinates are distances along two axes of the
+/// plane, so textures should repeat. Being unbounded it stays out of the BVH and can't be a
+/// light.
+pub struct Plane {
+    point: Vector3<f64>,
+    frame: Onb,
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Vector3<f64>, normal: Vector3<f64>, material: Arc<dyn Material>) -> Self {
+        Self {
+            point,
+            frame: Onb::new(&normal),
+            material,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let normal = self.frame.local(&Vector3::z());
+        let t = intersect_plane(r, t_min, t_max, &self.point, &normal)?;
+        let local = self.frame.to_local(&(r.at(t) - self.point));
+        Some(hit_record(
+            r,
+            t,
+            (local.x, local.y),
+            self.frame.local(&Vector3::x()),
+            self.frame.local(&Vector3::y()),
+            normal,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        None
+    }
+}
+
+/// Flat disk facing `normal`. `u` is the angle around the center and `v` the distance from
+/// it, both scaled to [0, 1].
+pub struct Disk {
+    center: Vector3<f64>,
+    radius: f64,
+    frame: Onb,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(
+        center: Vector3<f64>,
+        normal: Vector3<f64>,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            center,
+            radius,
+            frame: Onb::new(&normal),
+            material,
+        }
+    }
+
+    fn normal(&self) -> Vector3<f64> {
+        self.frame.local(&Vector3::z())
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = intersect_plane(r, t_min, t_max, &self.center, &self.normal())?;
+        let local = self.frame.to_local(&(r.at(t) - self.center));
+        let distance = local.x.hypot(local.y);
+        if distance > self.radius {
+            return None;
+        }
+
+        let phi = local.y.atan2(local.x).rem_euclid(2.0 * PI);
+        // The radial direction is undefined at the center, any axis will do there
+        let radial = if distance > 0.0 {
+            Vector3::new(local.x, local.y, 0.0) / distance
+        } else {
+            Vector3::x()
+        };
+        let dpdu = self.frame.local(&Vector3::new(-local.y, local.x, 0.0)) * 2.0 * PI;
+        let dpdv = self.frame.local(&radial) * self.radius;
+
+        Some(hit_record(
+            r,
+            t,
+            (phi / (2.0 * PI), distance / self.radius),
+            dpdu,
+            dpdv,
+            self.normal(),
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        // A circle extends along each axis by its radius times the sine to the normal
+        let normal = self.normal();
+        let extent = Vector3::from_fn(|axis, _| {
+            self.radius * (1.0 - normal[axis] * normal[axis]).max(0.0).sqrt()
+        });
+        Some(
+            AxisAlignedBoundingBox::new(self.center - extent, self.center + extent)
+                .padded(BBOX_PADDING),
+        )
+    }
+
+    fn pdf_value(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> f64 {
+        match self.hit(&Ray::new(*origin, *direction, 0.0), 0.001, f64::INFINITY) {
+            Some(hit) => area_pdf_value(
+                hit.t,
+                direction,
+                &self.normal(),
+                PI * self.radius * self.radius,
+            ),
+            None => 0.0,
+        }
+    }
+
+    fn random_direction(&self, origin: &Vector3<f64>) -> Vector3<f64> {
+        let r = self.radius * random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let point = self.center
+            + self
+                .frame
+                .local(&Vector3::new(r * phi.cos(), r * phi.sin(), 0.0));
+        point - origin
+    }
+}
+
+/// Axis-aligned box between two opposite corners, made of six outward facing quads.
+/// Rotate it with an instance.
+pub struct AxisAlignedBox {
+    sides: HittableList,
+}
+
+impl AxisAlignedBox {
+    pub fn new(a: Vector3<f64>, b: Vector3<f64>, material: Arc<dyn Material>) -> Self {
+        let min = a.inf(&b);
+        let max = a.sup(&b);
+        let dx = Vector3::new(max.x - min.x, 0.0, 0.0);
+        let dy = Vector3::new(0.0, max.y - min.y, 0.0);
+        let dz = Vector3::new(0.0, 0.0, max.z - min.z);
+
+        let mut sides = HittableList::new();
+        let mut add = |q: Vector3<f64>, u: Vector3<f64>, v: Vector3<f64>| {
+            sides.add(Arc::new(Quad::new(q, u, v, Arc::clone(&material))));
+        };
+        add(Vector3::new(min.x, min.y, max.z), dx, dy); // front
+        add(Vector3::new(max.x, min.y, max.z), -dz, dy); // right
+        add(Vector3::new(max.x, min.y, min.z), -dx, dy); // back
+        add(Vector3::new(min.x, min.y, min.z), dz, dy); // left
+        add(Vector3::new(min.x, max.y, max.z), dx, -dz); // top
+        add(Vector3::new(min.x, min.y, min.z), dx, dz); // bottom
+
+        Self { sides }
+    }
+}
+
+impl Hittable for AxisAlignedBox {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.sides.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        self.sides.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> f64 {
+        self.sides.pdf_value(origin, direction)
+    }
+
+    fn random_direction(&self, origin: &Vector3<f64>) -> Vector3<f64> {
+        self.sides.random_direction(origin)
+    }
+}
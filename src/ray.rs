@@ -4,18 +4,34 @@ use nalgebra::Vector3;
 
 use crate::{
     environment::Environment,
-    hittable::{Hittable, HittableList},
+    hittable::{HitRecord, Hittable, HittableList},
+    sampling::power_heuristic,
 };
 
 #[derive(Clone, Copy, Debug)]
 pub struct Ray {
     origin: Vector3<f64>,
     direction: Vector3<f64>,
+    /// Instant within the camera's shutter interval the ray samples, for motion blur
+    time: f64,
+    /// Color channel the path was restricted to by a dispersive hit, if any
+    channel: Option<usize>,
 }
 
 impl Ray {
-    pub fn new(origin: Vector3<f64>, direction: Vector3<f64>) -> Self {
-        Self { origin, direction }
+    pub fn new(origin: Vector3<f64>, direction: Vector3<f64>, time: f64) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+            channel: None,
+        }
+    }
+
+    /// Restricts the ray, and the rest of the path it starts, to a single color channel.
+    pub fn with_channel(mut self, channel: usize) -> Self {
+        self.channel = Some(channel);
+        self
     }
 
     pub fn at(&self, t: f64) -> Vector3<f64> {
@@ -30,24 +46,125 @@ impl Ray {
         self.direction
     }
 
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn channel(&self) -> Option<usize> {
+        self.channel
+    }
+
     pub fn color(
         &self,
-        world: &HittableList,
+        world: &dyn Hittable,
+        lights: &HittableList,
         env: &Arc<dyn Environment>,
         recursion_limit: i32,
+    ) -> Vector3<f64> {
+        self.trace(world, lights, env, recursion_limit, None)
+    }
+
+    /// `bsdf_pdf` is the density with which a non-specular bounce sampled this ray, or `None` for
+    /// camera rays and specular bounces. Light found by such a ray is weighted against
+    /// the light sampling done at the previous hit.
+    fn trace(
+        &self,
+        world: &dyn Hittable,
+        lights: &HittableList,
+        env: &Arc<dyn Environment>,
+        recursion_limit: i32,
+        bsdf_pdf: Option<f64>,
     ) -> Vector3<f64> {
         if recursion_limit <= 0 {
             return Vector3::new(0.0, 0.0, 0.0);
         }
 
-        if let Some(record) = world.hit(self, 0.001, f64::INFINITY) {
-            if let Some((scattered, attenuation)) = record.material.scatter(self, &record) {
-                attenuation.component_mul(&scattered.color(world, env, recursion_limit - 1))
+        let Some(record) = world.hit(self, 0.001, f64::INFINITY) else {
+            let background = env.background_color(self);
+            return match bsdf_pdf {
+                Some(pdf) => power_heuristic(pdf, env.pdf(&self.direction)) * background,
+                None => background,
+            };
+        };
+
+        let mut emitted = record.material.emitted(record.u, record.v, &record.p);
+        if let Some(pdf) = bsdf_pdf {
+            if emitted.max() > 0.0 {
+                emitted *= power_heuristic(pdf, lights.pdf_value(&self.origin, &self.direction));
+            }
+        }
+
+        let Some(sample) = record.material.sample(self, &record) else {
+            return emitted;
+        };
+
+        // Specular samples can't be weighted against light sampling, so light they find
+        // through the next bounce counts in full
+        let next_pdf = (!sample.is_specular).then_some(sample.pdf);
+        let direct = self.sample_lights(&record, world, lights, env);
+        let scattered = match self.channel {
+            Some(channel) => sample.scattered.with_channel(channel),
+            None => sample.scattered,
+        };
+        let indirect = sample.attenuation.component_mul(&scattered.trace(
+            world,
+            lights,
+            env,
+            recursion_limit - 1,
+            next_pdf,
+        ));
+        emitted + direct + indirect
+    }
+
+    /// Next-event estimation: one shadow ray towards the scene lights and one towards the
+    /// environment, each weighted against the material's own sampling and attenuated by
+    /// any volumes in between. Directions only reachable through specular lobes get a zero
+    /// `pdf` and are skipped.
+    fn sample_lights(
+        &self,
+        record: &HitRecord,
+        world: &dyn Hittable,
+        lights: &HittableList,
+        env: &Arc<dyn Environment>,
+    ) -> Vector3<f64> {
+        let mut direct = Vector3::new(0.0, 0.0, 0.0);
+
+        if !lights.is_empty() {
+            let direction = lights.random_direction(&record.p);
+            let bsdf_pdf = record.material.pdf(self, record, &direction);
+            let light_pdf = if bsdf_pdf > 0.0 {
+                lights.pdf_value(&record.p, &direction)
             } else {
-                Vector3::new(0.0, 0.0, 0.0)
+                0.0
+            };
+            if light_pdf > 0.0 {
+                let shadow_ray = Ray::new(record.p, direction, self.time);
+                if let Some(light) = lights.hit(&shadow_ray, 0.001, f64::INFINITY) {
+                    let transmittance = world.transmittance(&shadow_ray, 0.001, light.t - 0.001);
+                    if transmittance > 0.0 {
+                        let radiance = light.material.emitted(light.u, light.v, &light.p);
+                        let f = record.material.eval(self, record, &direction);
+                        direct += f.component_mul(&radiance)
+                            * (transmittance * power_heuristic(light_pdf, bsdf_pdf) / light_pdf);
+                    }
+                }
             }
-        } else {
-            env.background_color(self)
         }
+
+        if let Some(direction) = env.sample_direction() {
+            let bsdf_pdf = record.material.pdf(self, record, &direction);
+            let env_pdf = env.pdf(&direction);
+            if bsdf_pdf > 0.0 && env_pdf > 0.0 {
+                let shadow_ray = Ray::new(record.p, direction, self.time);
+                let transmittance = world.transmittance(&shadow_ray, 0.001, f64::INFINITY);
+                if transmittance > 0.0 {
+                    let f = record.material.eval(self, record, &direction);
+                    direct += f.component_mul(&env.background_color(&shadow_ray))
+                        * (transmittance * power_heuristic(env_pdf, bsdf_pdf) / env_pdf);
+                }
+            }
+        }
+
+        direct
     }
 }
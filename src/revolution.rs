@@ -0,0 +1,663 @@
+//! Surfaces of revolution around the Y axis through `center`. Instances orient them.
+//!
+//! None of them have a cheap way to pick points proportionally to solid angle, so as lights
+//! they sample the cone subtended by their bounding sphere. Directions in it that miss the
+//! shape just don't find the light.
+
+use std::{f64::consts::PI, sync::Arc};
+
+use nalgebra::Vector3;
+
+use crate::{
+    aabb::AxisAlignedBoundingBox,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    polynomial::{real_roots, solve_quadratic},
+    ray::Ray,
+    sampling::{cone_pdf, random_cone_direction, random_unit_vector, Onb},
+};
+
+/// A hit in the shape's local frame, relative to its center.
+struct LocalHit {
+    t: f64,
+    p: Vector3<f64>,
+    outward_normal: Vector3<f64>,
+    u: f64,
+    v: f64,
+    dpdu: Vector3<f64>,
+    dpdv: Vector3<f64>,
+}
+
+/// Angle around the Y axis as `u`, starting from -X like `Sphere`, with its tangent.
+fn azimuth(p: &Vector3<f64>) -> (f64, Vector3<f64>) {
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), 2.0 * PI * Vector3::new(p.z, 0.0, -p.x))
+}
+
+/// Hit on a flat cap of `radius` at height `y`, with polar UVs like `Disk`.
+fn cap_hit(
+    origin: &Vector3<f64>,
+    direction: &Vector3<f64>,
+    y: f64,
+    radius: f64,
+    normal_y: f64,
+) -> Option<LocalHit> {
+    if direction.y.abs() < 1e-12 {
+        return None;
+    }
+    let t = (y - origin.y) / direction.y;
+    let p = origin + t * direction;
+    let distance = p.x.hypot(p.z);
+    if distance > radius {
+        return None;
+    }
+
+    let (u, dpdu) = azimuth(&p);
+    let radial = if distance > 0.0 {
+        Vector3::new(p.x, 0.0, p.z) / distance
+    } else {
+        Vector3::x()
+    };
+    Some(LocalHit {
+        t,
+        p,
+        outward_normal: Vector3::new(0.0, normal_y, 0.0),
+        u,
+        v: distance / radius,
+        dpdu,
+        dpdv: radial * radius,
+    })
+}
+
+/// Profile tangent pointing up along the surface, scaled by the profile's `length`.
+fn profile_tangent(normal: &Vector3<f64>, length: f64) -> Vector3<f64> {
+    let rho = normal.x.hypot(normal.z);
+    let radial = if rho > 1e-12 {
+        Vector3::new(normal.x, 0.0, normal.z) / rho
+    } else {
+        Vector3::x()
+    };
+    (Vector3::new(0.0, rho, 0.0) - normal.y * radial) * length
+}
+
+/// Geometry of a surface of revolution, which `Revolution` turns into a `Hittable`.
+trait RevolutionShape {
+    fn center(&self) -> Vector3<f64>;
+    /// Extent of the shape from its center along Y (`.0`) and away from the axis (`.1`).
+    fn half_extents(&self) -> (f64, f64);
+    /// Every intersection of the local ray, in any order and range.
+    fn candidates(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> Vec<LocalHit>;
+
+    fn bounding_radius(&self) -> f64 {
+        let (height, radius) = self.half_extents();
+        height.hypot(radius)
+    }
+
+    fn cos_theta_max(&self, origin: &Vector3<f64>) -> Option<f64> {
+        let distance_squared = (self.center() - origin).magnitude_squared();
+        let radius_squared = self.bounding_radius().powi(2);
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
+}
+
+/// A `Cylinder`, `Cone`, `Torus` or `Capsule` made of `material`. Moves rays into the
+/// shape's local frame, keeps the closest candidate hit in range and samples the bounding
+/// sphere as a light.
+pub struct Revolution<S> {
+    shape: S,
+    material: Arc<dyn Material>,
+}
+
+impl<S> Revolution<S> {
+    pub fn new(shape: S, material: Arc<dyn Material>) -> Self {
+        Self { shape, material }
+    }
+}
+
+impl<S: RevolutionShape + Send + Sync> Hittable for Revolution<S> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let center = self.shape.center();
+        let origin = r.origin() - center;
+        let hit = self
+            .shape
+            .candidates(&origin, &r.direction())
+            .into_iter()
+            .filter(|hit| t_min <= hit.t && hit.t <= t_max)
+            .min_by(|a, b| a.t.total_cmp(&b.t))?;
+
+        let mut hit_record = HitRecord {
+            p: hit.p + center,
+            normal: Vector3::new(0.0, 0.0, 0.0),
+            t: hit.t,
+            u: hit.u,
+            v: hit.v,
+            dpdu: hit.dpdu,
+            dpdv: hit.dpdv,
+            front_face: false,
+            material: Arc::clone(&self.material),
+        };
+        hit_record.set_face_normal(r, hit.outward_normal.normalize());
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        let (height, radius) = self.shape.half_extents();
+        let extent = Vector3::new(radius, height, radius);
+        let center = self.shape.center();
+        Some(AxisAlignedBoundingBox::new(
+            center - extent,
+            center + extent,
+        ))
+    }
+
+    fn pdf_value(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> f64 {
+        if self
+            .hit(&Ray::new(*origin, *direction, 0.0), 0.001, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+        match self.shape.cos_theta_max(origin) {
+            Some(cos_theta_max) => cone_pdf(cos_theta_max),
+            None => 1.0 / (4.0 * PI),
+        }
+    }
+
+    fn random_direction(&self, origin: &Vector3<f64>) -> Vector3<f64> {
+        match self.shape.cos_theta_max(origin) {
+            Some(cos_theta_max) => Onb::new(&(self.shape.center() - origin))
+                .local(&random_cone_direction(cos_theta_max)),
+            None => random_unit_vector(),
+        }
+    }
+}
+
+/// Cylinder of `height` centered on `center`, optionally without its end caps.
+pub struct Cylinder {
+    center: Vector3<f64>,
+    radius: f64,
+    height: f64,
+    capped: bool,
+}
+
+impl Cylinder {
+    pub fn new(center: Vector3<f64>, radius: f64, height: f64) -> Self {
+        Self {
+            center,
+            radius,
+            height,
+            capped: true,
+        }
+    }
+
+    /// Open tubes are seen from the inside through their ends.
+    pub fn with_caps(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+}
+
+impl RevolutionShape for Cylinder {
+    fn center(&self) -> Vector3<f64> {
+        self.center
+    }
+
+    fn half_extents(&self) -> (f64, f64) {
+        (self.height / 2.0, self.radius)
+    }
+
+    fn candidates(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> Vec<LocalHit> {
+        let half_height = self.height / 2.0;
+        let a = direction.x * direction.x + direction.z * direction.z;
+        let half_b = origin.x * direction.x + origin.z * direction.z;
+        let c = origin.x * origin.x + origin.z * origin.z - self.radius * self.radius;
+
+        // Parallel rays have no side hits, rather than the linear fallback's spurious one
+        let sides = if a < 1e-12 {
+            Vec::new()
+        } else {
+            solve_quadratic(a, half_b, c)
+        };
+        let mut hits: Vec<LocalHit> = sides
+            .into_iter()
+            .filter_map(|t| {
+                let p = origin + t * direction;
+                if p.y.abs() > half_height {
+                    return None;
+                }
+                let (u, dpdu) = azimuth(&p);
+                Some(LocalHit {
+                    t,
+                    p,
+                    outward_normal: Vector3::new(p.x, 0.0, p.z),
+                    u,
+                    v: (p.y + half_height) / self.height,
+                    dpdu,
+                    dpdv: Vector3::new(0.0, self.height, 0.0),
+                })
+            })
+            .collect();
+
+        if self.capped {
+            hits.extend(cap_hit(origin, direction, half_height, self.radius, 1.0));
+            hits.extend(cap_hit(origin, direction, -half_height, self.radius, -1.0));
+        }
+        hits
+    }
+}
+
+/// Cone standing on a base of `radius` at `center`, with its apex `height` above.
+pub struct Cone {
+    center: Vector3<f64>,
+    radius: f64,
+    height: f64,
+    capped: bool,
+}
+
+impl Cone {
+    pub fn new(center: Vector3<f64>, radius: f64, height: f64) -> Self {
+        Self {
+            center,
+            radius,
+            height,
+            capped: true,
+        }
+    }
+
+    pub fn with_caps(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+}
+
+impl RevolutionShape for Cone {
+    // `Revolution` works around the middle of the bounding box
+    fn center(&self) -> Vector3<f64> {
+        self.center + Vector3::new(0.0, self.height / 2.0, 0.0)
+    }
+
+    fn half_extents(&self) -> (f64, f64) {
+        (self.height / 2.0, self.radius)
+    }
+
+    fn candidates(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> Vec<LocalHit> {
+        let half_height = self.height / 2.0;
+        // x² + z² = k² (apex - y)², with the apex at half_height
+        let k2 = (self.radius / self.height).powi(2);
+        let below_apex = half_height - origin.y;
+        let a =
+            direction.x * direction.x + direction.z * direction.z - k2 * direction.y * direction.y;
+        let half_b =
+            origin.x * direction.x + origin.z * direction.z + k2 * below_apex * direction.y;
+        let c = origin.x * origin.x + origin.z * origin.z - k2 * below_apex * below_apex;
+
+        let mut hits: Vec<LocalHit> = solve_quadratic(a, half_b, c)
+            .into_iter()
+            .filter_map(|t| {
+                let p = origin + t * direction;
+                // The equation also describes the mirrored cone above the apex
+                if p.y.abs() > half_height {
+                    return None;
+                }
+                let (u, dpdu) = azimuth(&p);
+                let outward_normal = Vector3::new(p.x, k2 * (half_height - p.y), p.z);
+                let slant = self.radius.hypot(self.height);
+                Some(LocalHit {
+                    t,
+                    p,
+                    outward_normal,
+                    u,
+                    v: (p.y + half_height) / self.height,
+                    dpdu,
+                    dpdv: profile_tangent(&outward_normal.normalize(), slant),
+                })
+            })
+            .collect();
+
+        if self.capped {
+            hits.extend(cap_hit(origin, direction, -half_height, self.radius, -1.0));
+        }
+        hits
+    }
+}
+
+/// Ring around the Y axis: a tube of `minor_radius` swept along a circle of `major_radius`.
+/// `u` goes around the ring and `v` around the tube, starting from its outer equator.
+pub struct Torus {
+    center: Vector3<f64>,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl Torus {
+    pub fn new(center: Vector3<f64>, major_radius: f64, minor_radius: f64) -> Self {
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl RevolutionShape for Torus {
+    fn center(&self) -> Vector3<f64> {
+        self.center
+    }
+
+    fn half_extents(&self) -> (f64, f64) {
+        (self.minor_radius, self.major_radius + self.minor_radius)
+    }
+
+    fn bounding_radius(&self) -> f64 {
+        self.major_radius + self.minor_radius
+    }
+
+    fn candidates(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> Vec<LocalHit> {
+        // Solve along the unit direction, starting where the ray enters the bounding sphere.
+        // Keeping the coefficients small is what keeps the quartic well conditioned.
+        let scale = direction.magnitude();
+        let d = direction / scale;
+        let bound = self.bounding_radius();
+        let bounds = solve_quadratic(1.0, origin.dot(&d), origin.dot(origin) - bound * bound);
+        let [enter, exit] = bounds[..] else {
+            return Vec::new();
+        };
+        let o = origin + enter * d;
+
+        // (|p|² + R² - r²)² = 4 R² (x² + z²), with p = o + s d
+        let r2 = self.major_radius * self.major_radius;
+        let n = o.dot(&d);
+        let e = o.dot(&o) + r2 - self.minor_radius * self.minor_radius;
+        let a_xz = d.x * d.x + d.z * d.z;
+        let b_xz = o.x * d.x + o.z * d.z;
+        let c_xz = o.x * o.x + o.z * o.z;
+        let coefficients = [
+            1.0,
+            4.0 * n,
+            4.0 * n * n + 2.0 * e - 4.0 * r2 * a_xz,
+            4.0 * n * e - 8.0 * r2 * b_xz,
+            e * e - 4.0 * r2 * c_xz,
+        ];
+
+        real_roots(&coefficients, 0.0, exit - enter)
+            .into_iter()
+            .map(|s| {
+                let p = o + s * d;
+                let (u, dpdu) = azimuth(&p);
+                let rho = p.x.hypot(p.z);
+                let radial = if rho > 1e-12 {
+                    Vector3::new(p.x, 0.0, p.z) / rho
+                } else {
+                    Vector3::x()
+                };
+                let outward_normal = p - self.major_radius * radial;
+                let theta = outward_normal.y.atan2(outward_normal.dot(&radial));
+                LocalHit {
+                    t: (enter + s) / scale,
+                    p,
+                    outward_normal,
+                    u,
+                    v: theta.rem_euclid(2.0 * PI) / (2.0 * PI),
+                    dpdu,
+                    dpdv: 2.0
+                        * PI
+                        * self.minor_radius
+                        * (theta.cos() * Vector3::y() - theta.sin() * radial),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Cylinder of `height` with hemispherical ends, so `height + 2 radius` tall overall.
+/// `v` follows the profile from the bottom pole to the top one.
+pub struct Capsule {
+    center: Vector3<f64>,
+    radius: f64,
+    height: f64,
+}
+
+impl Capsule {
+    pub fn new(center: Vector3<f64>, radius: f64, height: f64) -> Self {
+        Self {
+            center,
+            radius,
+            height,
+        }
+    }
+}
+
+impl RevolutionShape for Capsule {
+    fn center(&self) -> Vector3<f64> {
+        self.center
+    }
+
+    fn half_extents(&self) -> (f64, f64) {
+        (self.height / 2.0 + self.radius, self.radius)
+    }
+
+    fn bounding_radius(&self) -> f64 {
+        self.height / 2.0 + self.radius
+    }
+
+    fn candidates(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> Vec<LocalHit> {
+        let half_height = self.height / 2.0;
+        let r2 = self.radius * self.radius;
+        let mut ts = Vec::new();
+
+        // Side of the cylinder, only between the hemispheres
+        let a = direction.x * direction.x + direction.z * direction.z;
+        if a >= 1e-12 {
+            let half_b = origin.x * direction.x + origin.z * direction.z;
+            let c = origin.x * origin.x + origin.z * origin.z - r2;
+            ts.extend(
+                solve_quadratic(a, half_b, c)
+                    .into_iter()
+                    .filter(|t| (origin.y + t * direction.y).abs() <= half_height),
+            );
+        }
+
+        // Each end sphere, only beyond its end of the cylinder
+        for side in [-1.0, 1.0] {
+            let oc = origin - Vector3::new(0.0, side * half_height, 0.0);
+            ts.extend(
+                solve_quadratic(
+                    direction.dot(direction),
+                    oc.dot(direction),
+                    oc.dot(&oc) - r2,
+                )
+                .into_iter()
+                .filter(|t| side * (origin.y + t * direction.y) >= half_height),
+            );
+        }
+
+        // Arc length of the profile, for v
+        let length = self.height + PI * self.radius;
+        ts.into_iter()
+            .map(|t| {
+                let p = origin + t * direction;
+                let axis_point = Vector3::new(0.0, p.y.clamp(-half_height, half_height), 0.0);
+                let normal = (p - axis_point) / self.radius;
+                let (u, dpdu) = azimuth(&p);
+                // Angle from the bottom pole around the profile's end arcs
+                let theta = (-normal.y).clamp(-1.0, 1.0).acos();
+                let arc = if p.y < -half_height {
+                    self.radius * theta
+                } else if p.y > half_height {
+                    self.height + self.radius * theta
+                } else {
+                    self.radius * PI / 2.0 + p.y + half_height
+                };
+                LocalHit {
+                    t,
+                    p,
+                    outward_normal: normal,
+                    u,
+                    v: arc / length,
+                    dpdu,
+                    dpdv: profile_tangent(&normal, length),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Dielectric;
+
+    const CENTER: Vector3<f64> = Vector3::new(1.0, 2.0, 3.0);
+
+    fn hit<S: RevolutionShape + Send + Sync>(
+        shape: S,
+        origin: Vector3<f64>,
+        direction: Vector3<f64>,
+    ) -> Option<HitRecord> {
+        Revolution::new(shape, Arc::new(Dielectric::new(1.5))).hit(
+            &Ray::new(CENTER + origin, direction, 0.0),
+            0.001,
+            f64::INFINITY,
+        )
+    }
+
+    /// Checks `t`, the world space point, the normal and the UVs of a hit.
+    fn assert_hit(
+        hit: Option<HitRecord>,
+        t: f64,
+        p: Vector3<f64>,
+        normal: Vector3<f64>,
+        (u, v): (f64, f64),
+    ) {
+        let hit = hit.expect("ray should hit");
+        assert!((hit.t - t).abs() < 1e-9, "t: {} != {}", hit.t, t);
+        assert!((hit.p - (CENTER + p)).norm() < 1e-9, "p: {:?}", hit.p);
+        assert!(
+            (hit.normal - normal.normalize()).norm() < 1e-9,
+            "normal: {:?}",
+            hit.normal
+        );
+        assert!(hit.front_face);
+        assert!((hit.u - u).abs() < 1e-9, "u: {} != {}", hit.u, u);
+        assert!((hit.v - v).abs() < 1e-9, "v: {} != {}", hit.v, v);
+    }
+
+    #[test]
+    fn cylinder_side_and_cap() {
+        let cylinder = || Cylinder::new(CENTER, 1.0, 2.0);
+        // t is in units of the unnormalized direction
+        assert_hit(
+            hit(
+                cylinder(),
+                Vector3::new(0.0, 0.5, -5.0),
+                Vector3::new(0.0, 0.0, 2.0),
+            ),
+            2.0,
+            Vector3::new(0.0, 0.5, -1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+            (0.75, 0.75),
+        );
+        assert_hit(
+            hit(
+                cylinder(),
+                Vector3::new(0.0, 5.0, 0.5),
+                Vector3::new(0.0, -1.0, 0.0),
+            ),
+            4.0,
+            Vector3::new(0.0, 1.0, 0.5),
+            Vector3::new(0.0, 1.0, 0.0),
+            (0.25, 0.5),
+        );
+        // Down an open tube, parallel to its side
+        assert!(hit(
+            cylinder().with_caps(false),
+            Vector3::new(0.0, 5.0, 0.5),
+            Vector3::new(0.0, -1.0, 0.0),
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn cone_side() {
+        // Halfway up, the cone of radius 1 and height 2 has a radius of 0.5
+        assert_hit(
+            hit(
+                Cone::new(CENTER, 1.0, 2.0),
+                Vector3::new(0.0, 1.0, -5.0),
+                Vector3::new(0.0, 0.0, 1.0),
+            ),
+            4.5,
+            Vector3::new(0.0, 1.0, -0.5),
+            Vector3::new(0.0, 1.0, -2.0),
+            (0.75, 0.5),
+        );
+    }
+
+    #[test]
+    fn torus_from_far_away() {
+        let torus = || Torus::new(CENTER, 2.0, 0.5);
+        // Far outside the bounding sphere, so the quartic is solved from its entry point
+        assert_hit(
+            hit(
+                torus(),
+                Vector3::new(0.0, 100.0, 2.0),
+                Vector3::new(0.0, -1.0, 0.0),
+            ),
+            99.5,
+            Vector3::new(0.0, 0.5, 2.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            (0.25, 0.25),
+        );
+        assert_hit(
+            hit(
+                torus(),
+                Vector3::new(0.0, 0.0, 100.0),
+                Vector3::new(0.0, 0.0, -3.0),
+            ),
+            32.5,
+            Vector3::new(0.0, 0.0, 2.5),
+            Vector3::new(0.0, 0.0, 1.0),
+            (0.25, 0.0),
+        );
+        // Through the hole
+        assert!(hit(
+            torus(),
+            Vector3::new(0.0, 100.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn capsule_side_and_end() {
+        let capsule = || Capsule::new(CENTER, 0.5, 2.0);
+        let length = 2.0 + PI / 2.0;
+        assert_hit(
+            hit(
+                capsule(),
+                Vector3::new(0.0, 0.0, -5.0),
+                Vector3::new(0.0, 0.0, 1.0),
+            ),
+            4.5,
+            Vector3::new(0.0, 0.0, -0.5),
+            Vector3::new(0.0, 0.0, -1.0),
+            (0.75, 0.5),
+        );
+        // 30 degrees up the top hemisphere
+        let z = 0.5 * (PI / 6.0).cos();
+        assert_hit(
+            hit(
+                capsule(),
+                Vector3::new(0.0, 1.25, -5.0),
+                Vector3::new(0.0, 0.0, 1.0),
+            ),
+            5.0 - z,
+            Vector3::new(0.0, 1.25, -z),
+            Vector3::new(0.0, 0.25, -z),
+            (0.75, (2.0 + PI / 3.0) / length),
+        );
+    }
+}
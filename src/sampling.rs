@@ -0,0 +1,95 @@
+use std::f64::consts::PI;
+
+use nalgebra::Vector3;
+
+use crate::utils::random_double;
+
+/// Orthonormal basis whose `w` axis is aligned with a given direction.
+pub struct Onb {
+    u: Vector3<f64>,
+    v: Vector3<f64>,
+    w: Vector3<f64>,
+}
+
+impl Onb {
+    pub fn new(n: &Vector3<f64>) -> Self {
+        let w = n.normalize();
+        let a = if w.x.abs() > 0.9 {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(&a).normalize();
+        let u = w.cross(&v);
+        Self { u, v, w }
+    }
+
+    /// Basis around `n` whose `u` axis follows `tangent` projected onto the tangent plane.
+    /// Falls back to an arbitrary orientation when the tangent is (nearly) parallel to `n`.
+    pub fn from_normal_tangent(n: &Vector3<f64>, tangent: &Vector3<f64>) -> Self {
+        let w = n.normalize();
+        let projected = tangent - tangent.dot(&w) * w;
+        if projected.magnitude_squared() < 1e-16 {
+            return Self::new(n);
+        }
+        let u = projected.normalize();
+        let v = w.cross(&u);
+        Self { u, v, w }
+    }
+
+    /// Converts a vector expressed in this basis to world space.
+    pub fn local(&self, a: &Vector3<f64>) -> Vector3<f64> {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+
+    /// Inverse of `local`: expresses a world space vector in this basis.
+    pub fn to_local(&self, a: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+    }
+}
+
+/// Samples the hemisphere around +z with density cos(theta) / pi.
+pub fn random_cosine_direction() -> Vector3<f64> {
+    let r1 = random_double();
+    let r2 = random_double();
+    let phi = 2.0 * PI * r1;
+    let sin_theta = r2.sqrt();
+    Vector3::new(
+        phi.cos() * sin_theta,
+        phi.sin() * sin_theta,
+        (1.0 - r2).sqrt(),
+    )
+}
+
+/// Uniformly samples a direction on the unit sphere.
+pub fn random_unit_vector() -> Vector3<f64> {
+    let z = 1.0 - 2.0 * random_double();
+    let phi = 2.0 * PI * random_double();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    Vector3::new(phi.cos() * r, phi.sin() * r, z)
+}
+
+/// Uniformly samples a direction inside the cone around +z with the given half-angle cosine.
+pub fn random_cone_direction(cos_theta_max: f64) -> Vector3<f64> {
+    let r1 = random_double();
+    let r2 = random_double();
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
+    let phi = 2.0 * PI * r1;
+    let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+    Vector3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z)
+}
+
+pub fn cone_pdf(cos_theta_max: f64) -> f64 {
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+}
+
+/// Power heuristic (beta = 2) weight for a sample drawn with `pdf_a` against `pdf_b`.
+pub fn power_heuristic(pdf_a: f64, pdf_b: f64) -> f64 {
+    let a2 = pdf_a * pdf_a;
+    let b2 = pdf_b * pdf_b;
+    if a2 + b2 > 0.0 {
+        a2 / (a2 + b2)
+    } else {
+        0.0
+    }
+}
@@ -0,0 +1,279 @@
+mod distance_fields;
+mod materials;
+mod media;
+mod shapes;
+mod textures;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use nalgebra::Vector3;
+use serde::Deserialize;
+
+use crate::{
+    camera::Camera,
+    environment::{ConstantEnvironment, Environment, GradientEnvironment, SkyEnvironment},
+    hittable::{Hittable, HittableList},
+    tonemap::{ToneMapper, ToneMapping},
+};
+
+use materials::{build_material, MaterialDescription};
+use shapes::{ObjectContext, ObjectDescription};
+use textures::{build_texture, TextureDescription};
+
+type Color = [f64; 3];
+type Point = [f64; 3];
+
+/// On-disk scene description, deserialized from TOML.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    #[serde(default)]
+    pub render: RenderSettings,
+    camera: CameraDescription,
+    #[serde(default)]
+    environment: EnvironmentDescription,
+    // Sorted so that textures drawing random seeds are built in the same order on every run
+    #[serde(default)]
+    textures: BTreeMap<String, TextureDescription>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default)]
+    objects: Vec<ObjectDescription>,
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: i32,
+    pub tone_mapping: ToneMapper,
+    /// Exposure compensation in stops, applied before tone mapping
+    pub exposure: f64,
+    /// Input level mapped to white by the extended Reinhard operator
+    pub white_point: f64,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            tone_mapping: ToneMapper::None,
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        ToneMapping {
+            operator: self.tone_mapping,
+            exposure: self.exposure,
+            white_point: self.white_point,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    lookfrom: Point,
+    lookat: Point,
+    #[serde(default = "default_vup")]
+    vup: Point,
+    vfov: f64,
+    #[serde(default)]
+    aperture: f64,
+    // Defaults to the distance between `lookfrom` and `lookat`
+    focus_distance: Option<f64>,
+    // Time interval of the exposure; objects move from time 0 to time 1
+    #[serde(default)]
+    shutter_open: f64,
+    #[serde(default)]
+    shutter_close: f64,
+}
+
+fn default_vup() -> Point {
+    [0.0, 1.0, 0.0]
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum EnvironmentDescription {
+    Constant {
+        color: Color,
+    },
+    Gradient {
+        sky_top: Color,
+        sky_bottom: Color,
+    },
+    Sky {
+        sky_color: Color,
+        sun_color: Color,
+        sun_direction: Point,
+        sun_angular_size: f64,
+    },
+}
+
+impl Default for EnvironmentDescription {
+    fn default() -> Self {
+        Self::Gradient {
+            sky_top: [0.5, 0.7, 1.0],
+            sky_bottom: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// Everything needed to render, built from a `SceneFile`.
+pub struct Scene {
+    pub world: Arc<dyn Hittable>,
+    // Emissive objects, sampled directly at diffuse hits
+    pub lights: HittableList,
+    pub camera: Camera,
+    pub environment: Arc<dyn Environment>,
+    pub settings: RenderSettings,
+}
+
+impl SceneFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+        let mut scene: Self =
+            toml::from_str(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+        scene.base_dir = path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+        Ok(scene)
+    }
+
+    pub fn build(self) -> Result<Scene, String> {
+        let settings = self.render;
+        // Pixel coordinates are divided by the width and height minus one
+        if settings.width < 2 || settings.height < 2 {
+            return Err("render: width and height must be at least 2 pixels".to_string());
+        }
+        // Pixels are averaged over their samples
+        if settings.samples_per_pixel == 0 {
+            return Err("render.samples_per_pixel: must be at least 1".to_string());
+        }
+        if settings.max_depth <= 0 {
+            return Err("render.max_depth: must be positive".to_string());
+        }
+        if settings.white_point <= 0.0 {
+            return Err("render.white_point: must be positive".to_string());
+        }
+
+        let mut textures = HashMap::new();
+        for (name, description) in &self.textures {
+            let texture = build_texture(description, &self.base_dir)
+                .map_err(|e| format!("textures.{}.{}", name, e))?;
+            textures.insert(name.as_str(), texture);
+        }
+
+        let mut materials = HashMap::new();
+        for (name, description) in &self.materials {
+            let material = build_material(description, &textures)
+                .map_err(|e| format!("materials.{}.{}", name, e))?;
+            materials.insert(name.as_str(), material);
+        }
+
+        let mut context = ObjectContext {
+            materials,
+            meshes: HashMap::new(),
+        };
+        let mut world = HittableList::new();
+        let mut lights = HittableList::new();
+        for (i, object) in self.objects.iter().enumerate() {
+            let built = self.build_object(object, &format!("objects[{}]", i), &mut context)?;
+            for light in built.lights {
+                lights.add(light);
+            }
+            world.add(built.hittable);
+        }
+
+        let camera = self.camera.build(settings.aspect_ratio())?;
+
+        Ok(Scene {
+            world: world.into_bvh(),
+            lights,
+            camera,
+            environment: build_environment(&self.environment),
+            settings,
+        })
+    }
+}
+
+impl CameraDescription {
+    fn build(&self, aspect_ratio: f64) -> Result<Camera, String> {
+        // Moving objects are only defined between time 0 and time 1
+        if !(0.0 <= self.shutter_open
+            && self.shutter_open <= self.shutter_close
+            && self.shutter_close <= 1.0)
+        {
+            return Err(
+                "camera: shutter times must satisfy 0 <= shutter_open <= shutter_close <= 1"
+                    .to_string(),
+            );
+        }
+
+        let lookfrom = Vector3::from(self.lookfrom);
+        let lookat = Vector3::from(self.lookat);
+        let focus_distance = self
+            .focus_distance
+            .unwrap_or_else(|| (lookfrom - lookat).magnitude());
+
+        Ok(Camera::new(
+            lookfrom,
+            lookat,
+            Vector3::from(self.vup),
+            self.vfov,
+            aspect_ratio,
+            self.aperture,
+            focus_distance,
+        )
+        .with_shutter(self.shutter_open, self.shutter_close))
+    }
+}
+
+fn build_environment(description: &EnvironmentDescription) -> Arc<dyn Environment> {
+    match description {
+        EnvironmentDescription::Constant { color } => {
+            Arc::new(ConstantEnvironment::new(Vector3::from(*color)))
+        }
+        EnvironmentDescription::Gradient {
+            sky_top,
+            sky_bottom,
+        } => Arc::new(GradientEnvironment::new(
+            Vector3::from(*sky_top),
+            Vector3::from(*sky_bottom),
+        )),
+        EnvironmentDescription::Sky {
+            sky_color,
+            sun_color,
+            sun_direction,
+            sun_angular_size,
+        } => Arc::new(SkyEnvironment::new(
+            Vector3::from(*sky_color),
+            Vector3::from(*sun_color),
+            Vector3::from(*sun_direction),
+            *sun_angular_size,
+        )),
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use nalgebra::Vector3;
+use serde::Deserialize;
+
+use crate::sdf::{
+    Blend, CapsuleSdf, DistanceField, Mandelbulb, RoundedBoxSdf, SmoothUnion, SphereSdf, TorusSdf,
+};
+
+use super::Point;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(super) enum SdfDescription {
+    Sphere {
+        center: Point,
+        radius: f64,
+    },
+    Box {
+        center: Point,
+        half_extents: Point,
+        #[serde(default)]
+        rounding: f64,
+    },
+    Torus {
+        center: Point,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Capsule {
+        a: Point,
+        b: Point,
+        radius: f64,
+    },
+    Mandelbulb {
+        center: Point,
+        #[serde(default = "default_mandelbulb_scale")]
+        scale: f64,
+        #[serde(default = "default_mandelbulb_power")]
+        power: f64,
+        #[serde(default = "default_mandelbulb_iterations")]
+        iterations: u32,
+    },
+    /// Melts all `fields` together, with fillets as wide as `smoothness`
+    SmoothUnion {
+        fields: Vec<SdfDescription>,
+        #[serde(default)]
+        smoothness: f64,
+    },
+    /// Morphs from `a` at a `factor` of 0 to `b` at 1
+    Blend {
+        a: Box<SdfDescription>,
+        b: Box<SdfDescription>,
+        factor: f64,
+    },
+}
+
+fn default_mandelbulb_scale() -> f64 {
+    1.0
+}
+
+fn default_mandelbulb_power() -> f64 {
+    8.0
+}
+
+fn default_mandelbulb_iterations() -> u32 {
+    12
+}
+
+pub(super) fn build_distance_field(
+    description: &SdfDescription,
+    key: &str,
+) -> Result<Arc<dyn DistanceField>, String> {
+    Ok(match description {
+        SdfDescription::Sphere { center, radius } => {
+            Arc::new(SphereSdf::new(Vector3::from(*center), *radius))
+        }
+        SdfDescription::Box {
+            center,
+            half_extents,
+            rounding,
+        } => Arc::new(RoundedBoxSdf::new(
+            Vector3::from(*center),
+            Vector3::from(*half_extents),
+            *rounding,
+        )),
+        SdfDescription::Torus {
+            center,
+            major_radius,
+            minor_radius,
+        } => Arc::new(TorusSdf::new(
+            Vector3::from(*center),
+            *major_radius,
+            *minor_radius,
+        )),
+        SdfDescription::Capsule { a, b, radius } => Arc::new(CapsuleSdf::new(
+            Vector3::from(*a),
+            Vector3::from(*b),
+            *radius,
+        )),
+        SdfDescription::Mandelbulb {
+            center,
+            scale,
+            power,
+            iterations,
+        } => Arc::new(Mandelbulb::new(
+            Vector3::from(*center),
+            *scale,
+            *power,
+            *iterations,
+        )),
+        SdfDescription::SmoothUnion { fields, smoothness } => {
+            let mut fields = fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| build_distance_field(field, &format!("{}.fields[{}]", key, i)));
+            let first = fields
+                .next()
+                .ok_or_else(|| format!("{}.fields: needs at least one field", key))??;
+            fields.try_fold(first, |union, field| {
+                Ok::<_, String>(Arc::new(SmoothUnion::new(union, field?, *smoothness)) as Arc<_>)
+            })?
+        }
+        SdfDescription::Blend { a, b, factor } => Arc::new(Blend::new(
+            build_distance_field(a, &format!("{}.a", key))?,
+            build_distance_field(b, &format!("{}.b", key))?,
+            *factor,
+        )),
+    })
+}
@@ -0,0 +1,218 @@
+use std::{collections::HashMap, sync::Arc};
+
+use nalgebra::Vector3;
+use serde::Deserialize;
+
+use crate::{
+    material::{
+        Conductor, ConductorPreset, Dielectric, DiffuseLight, Lambertian, Material, Metal,
+        RoughDielectric,
+    },
+    medium::{HenyeyGreenstein, Isotropic},
+    principled::Principled,
+    texture::{SolidColor, Texture},
+};
+
+use super::{
+    textures::{ColorOrTexture, ScalarOrTexture},
+    Color,
+};
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(super) enum MaterialDescription {
+    Lambertian {
+        albedo: ColorOrTexture,
+    },
+    Metal {
+        albedo: ColorOrTexture,
+        #[serde(default)]
+        fuzz: ScalarOrTexture,
+    },
+    Dielectric {
+        ior: f64,
+        // Per unit of distance, e.g. [0.0, 0.2, 0.4] for amber glass
+        absorption: Option<Color>,
+        abbe_number: Option<f64>,
+    },
+    /// Either a named `preset` metal or an explicit complex IOR
+    Conductor {
+        preset: Option<ConductorPreset>,
+        eta: Option<Color>,
+        k: Option<Color>,
+        #[serde(default)]
+        roughness: ScalarOrTexture,
+    },
+    RoughDielectric {
+        ior: f64,
+        #[serde(default)]
+        roughness: ScalarOrTexture,
+    },
+    /// Parameters left out keep `Principled`'s defaults
+    Principled {
+        base_color: ColorOrTexture,
+        metallic: Option<ScalarOrTexture>,
+        roughness: Option<ScalarOrTexture>,
+        specular: Option<ScalarOrTexture>,
+        specular_tint: Option<ScalarOrTexture>,
+        sheen: Option<ScalarOrTexture>,
+        sheen_tint: Option<ScalarOrTexture>,
+        clearcoat: Option<ScalarOrTexture>,
+        clearcoat_roughness: Option<ScalarOrTexture>,
+        transmission: Option<ScalarOrTexture>,
+        ior: Option<f64>,
+    },
+    /// Phase functions, for use as the boundary material of a medium
+    Isotropic {
+        albedo: ColorOrTexture,
+    },
+    HenyeyGreenstein {
+        albedo: ColorOrTexture,
+        // In (-1, 1), positive for forward scattering
+        anisotropy: f64,
+    },
+    DiffuseLight {
+        emit: ColorOrTexture,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+/// Errors are returned relative to the material's key, e.g. `albedo: unknown texture`.
+pub(super) fn build_material(
+    description: &MaterialDescription,
+    textures: &HashMap<&str, Arc<dyn Texture>>,
+) -> Result<Arc<dyn Material>, String> {
+    let lookup = |name: &str, key: &str| -> Result<Arc<dyn Texture>, String> {
+        textures
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("{}: unknown texture '{}'", key, name))
+    };
+    let texture = |albedo: &ColorOrTexture, key: &str| -> Result<Arc<dyn Texture>, String> {
+        match albedo {
+            ColorOrTexture::Color(color) => Ok(Arc::new(SolidColor::new(Vector3::from(*color)))),
+            ColorOrTexture::Texture(name) => lookup(name, key),
+        }
+    };
+    let scalar = |value: &ScalarOrTexture, key: &str| -> Result<Arc<dyn Texture>, String> {
+        match value {
+            ScalarOrTexture::Scalar(x) => Ok(Arc::new(SolidColor::new(Vector3::repeat(*x)))),
+            ScalarOrTexture::Texture(name) => lookup(name, key),
+        }
+    };
+
+    let material: Arc<dyn Material> = match description {
+        MaterialDescription::Lambertian { albedo } => {
+            Arc::new(Lambertian::new(texture(albedo, "albedo")?))
+        }
+        MaterialDescription::Metal { albedo, fuzz } => Arc::new(Metal::new(
+            texture(albedo, "albedo")?,
+            scalar(fuzz, "fuzz")?,
+        )),
+        MaterialDescription::Dielectric {
+            ior,
+            absorption,
+            abbe_number,
+        } => {
+            let mut dielectric = Dielectric::new(*ior);
+            if let Some(absorption) = absorption {
+                dielectric = dielectric.with_absorption(Vector3::from(*absorption));
+            }
+            if let Some(abbe_number) = abbe_number {
+                if *abbe_number <= 0.0 {
+                    return Err("abbe_number: must be positive".to_string());
+                }
+                dielectric = dielectric.with_abbe_number(*abbe_number);
+            }
+            Arc::new(dielectric)
+        }
+        MaterialDescription::Conductor {
+            preset,
+            eta,
+            k,
+            roughness,
+        } => {
+            let roughness = scalar(roughness, "roughness")?;
+            match (preset, eta, k) {
+                (Some(preset), None, None) => Arc::new(Conductor::from_preset(*preset, roughness)),
+                (None, Some(eta), Some(k)) => Arc::new(Conductor::new(
+                    Vector3::from(*eta),
+                    Vector3::from(*k),
+                    roughness,
+                )),
+                _ => return Err("preset: expected either a preset or both eta and k".to_string()),
+            }
+        }
+        MaterialDescription::RoughDielectric { ior, roughness } => {
+            Arc::new(RoughDielectric::new(*ior, scalar(roughness, "roughness")?))
+        }
+        MaterialDescription::Principled {
+            base_color,
+            metallic,
+            roughness,
+            specular,
+            specular_tint,
+            sheen,
+            sheen_tint,
+            clearcoat,
+            clearcoat_roughness,
+            transmission,
+            ior,
+        } => {
+            let mut material = Principled::new(texture(base_color, "base_color")?);
+            let parameters = [
+                (
+                    metallic,
+                    "metallic",
+                    Principled::with_metallic as fn(_, _) -> _,
+                ),
+                (roughness, "roughness", Principled::with_roughness),
+                (specular, "specular", Principled::with_specular),
+                (
+                    specular_tint,
+                    "specular_tint",
+                    Principled::with_specular_tint,
+                ),
+                (sheen, "sheen", Principled::with_sheen),
+                (sheen_tint, "sheen_tint", Principled::with_sheen_tint),
+                (clearcoat, "clearcoat", Principled::with_clearcoat),
+                (
+                    clearcoat_roughness,
+                    "clearcoat_roughness",
+                    Principled::with_clearcoat_roughness,
+                ),
+                (transmission, "transmission", Principled::with_transmission),
+            ];
+            for (value, key, with) in parameters {
+                if let Some(value) = value {
+                    material = with(material, scalar(value, key)?);
+                }
+            }
+            if let Some(ior) = ior {
+                material = material.with_ior(*ior);
+            }
+            Arc::new(material)
+        }
+        MaterialDescription::Isotropic { albedo } => {
+            Arc::new(Isotropic::new(texture(albedo, "albedo")?))
+        }
+        MaterialDescription::HenyeyGreenstein { albedo, anisotropy } => {
+            if anisotropy.abs() >= 1.0 {
+                return Err("anisotropy: must be between -1 and 1".to_string());
+            }
+            Arc::new(HenyeyGreenstein::new(
+                texture(albedo, "albedo")?,
+                *anisotropy,
+            ))
+        }
+        MaterialDescription::DiffuseLight { emit, intensity } => {
+            Arc::new(DiffuseLight::new(texture(emit, "emit")?, *intensity))
+        }
+    };
+    Ok(material)
+}
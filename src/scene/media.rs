@@ -0,0 +1,80 @@
+use std::{path::PathBuf, sync::Arc};
+
+use serde::Deserialize;
+
+use crate::{
+    hittable::Hittable,
+    medium::{ConstantMedium, DensityField, HeterogeneousMedium, NoiseDensity, VoxelGrid},
+};
+
+use super::{
+    shapes::{ObjectContext, ObjectDescription},
+    SceneFile,
+};
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(super) enum DensityFieldDescription {
+    Noise {
+        scale: f64,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+    },
+    /// Mitsuba `.vol` file, relative to the scene file
+    Grid { path: PathBuf },
+}
+
+fn default_octaves() -> u32 {
+    5
+}
+
+impl SceneFile {
+    /// Fills `boundary` with a volume scattering by the boundary's material, uniform without a
+    /// density `field`. Errors are prefixed with `key` like `build_object`'s.
+    pub(super) fn build_medium(
+        &self,
+        boundary: &ObjectDescription,
+        density: f64,
+        field: Option<&DensityFieldDescription>,
+        key: &str,
+        context: &mut ObjectContext,
+    ) -> Result<Arc<dyn Hittable>, String> {
+        if density <= 0.0 {
+            return Err(format!("{}.density: must be positive", key));
+        }
+        let boundary_key = format!("{}.boundary", key);
+        let phase_function = match boundary {
+            ObjectDescription::Mesh { material, .. } => material.as_deref(),
+            boundary => boundary.material(),
+        }
+        .and_then(|material| context.materials.get(material).cloned());
+        // Checked after building, which reports unknown materials with the right key.
+        // Emissive boundaries don't make the volume a light.
+        let boundary = self
+            .build_object(boundary, &boundary_key, context)?
+            .hittable;
+        let phase_function = phase_function
+            .ok_or_else(|| format!("{}: needs a material to scatter with", boundary_key))?;
+
+        Ok(match field {
+            Some(field) => {
+                let field: Arc<dyn DensityField> = match field {
+                    DensityFieldDescription::Noise { scale, octaves } => {
+                        Arc::new(NoiseDensity::new(*scale, *octaves))
+                    }
+                    DensityFieldDescription::Grid { path } => Arc::new(
+                        VoxelGrid::load(&self.base_dir.join(path))
+                            .map_err(|e| format!("{}.field.path: {}", key, e))?,
+                    ),
+                };
+                Arc::new(HeterogeneousMedium::new(
+                    boundary,
+                    field,
+                    density,
+                    phase_function,
+                ))
+            }
+            None => Arc::new(ConstantMedium::new(boundary, density, phase_function)),
+        })
+    }
+}
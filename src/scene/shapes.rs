@@ -0,0 +1,532 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use nalgebra::{Matrix4, UnitQuaternion, Vector3};
+use serde::Deserialize;
+
+use crate::{
+    csg::{Csg, Operation},
+    heightfield::{HeightMap, Heightfield},
+    hittable::Hittable,
+    material::{Lambertian, Material},
+    obj,
+    quad::{AxisAlignedBox, Disk, Plane, Quad},
+    revolution::{Capsule, Cone, Cylinder, Revolution, Torus},
+    sdf::SdfObject,
+    sphere::Sphere,
+    transform::{Pose, Transform},
+    triangle::Triangle,
+};
+
+use super::{
+    distance_fields::{build_distance_field, SdfDescription},
+    materials::MaterialDescription,
+    media::DensityFieldDescription,
+    Point, SceneFile,
+};
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(super) enum ObjectDescription {
+    /// Moves from `center` to `center_end` while the shutter is open. Moving spheres aren't
+    /// sampled as lights.
+    Sphere {
+        center: Point,
+        radius: f64,
+        material: String,
+        center_end: Option<Point>,
+    },
+    Triangle {
+        vertices: [Point; 3],
+        normals: Option<[Point; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+        material: String,
+    },
+    Mesh {
+        path: PathBuf,
+        // Used for faces without an MTL material
+        material: Option<String>,
+    },
+    /// Parallelogram with the corner `q` and edges `u` and `v`, facing `u × v`
+    Quad {
+        q: Point,
+        u: Point,
+        v: Point,
+        material: String,
+    },
+    /// Infinite plane, never sampled as a light
+    Plane {
+        point: Point,
+        normal: Point,
+        material: String,
+    },
+    Disk {
+        center: Point,
+        normal: Point,
+        radius: f64,
+        material: String,
+    },
+    /// Axis-aligned box between two opposite corners
+    Box {
+        min: Point,
+        max: Point,
+        material: String,
+    },
+    /// Upright cylinder centered on `center`
+    Cylinder {
+        center: Point,
+        radius: f64,
+        height: f64,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: String,
+    },
+    /// Upright cone with its base centered on `center`
+    Cone {
+        center: Point,
+        radius: f64,
+        height: f64,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: String,
+    },
+    /// Ring lying flat around `center`
+    Torus {
+        center: Point,
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+    /// Upright cylinder of `height` with hemispherical ends
+    Capsule {
+        center: Point,
+        radius: f64,
+        height: f64,
+        material: String,
+    },
+    /// Fills a closed, convex object with a volume scattering by that object's material
+    ConstantMedium {
+        boundary: Box<ObjectDescription>,
+        density: f64,
+    },
+    /// Places another object with scale, then rotation (in degrees about x, y, then z), then
+    /// translation, then an optional row-major `matrix`. Meshes are loaded once and shared
+    /// between all instances of them. With an `end` pose the instance moves to it while the
+    /// shutter is open, and its lights are no longer sampled.
+    Instance {
+        object: Box<ObjectDescription>,
+        #[serde(default)]
+        translate: Point,
+        #[serde(default)]
+        rotate: Point,
+        #[serde(default)]
+        scale: Scale,
+        matrix: Option<[[f64; 4]; 4]>,
+        end: Option<PoseDescription>,
+    },
+    /// Like `ConstantMedium`, with `density` scaling a spatially varying field
+    HeterogeneousMedium {
+        boundary: Box<ObjectDescription>,
+        density: f64,
+        field: DensityFieldDescription,
+    },
+    /// Surface where a signed distance field is zero, found by sphere tracing
+    Sdf {
+        field: SdfDescription,
+        material: String,
+    },
+    /// Terrain spanning `size[0]` along X and `size[2]` along Z around `center`, rising up to
+    /// `size[1]` above it
+    Heightfield {
+        map: HeightMapDescription,
+        #[serde(default)]
+        center: Point,
+        size: Point,
+        material: String,
+    },
+    /// Solids combined from two closed objects, each keeping its own material. Lights inside
+    /// them are not sampled.
+    Union {
+        left: Box<ObjectDescription>,
+        right: Box<ObjectDescription>,
+    },
+    Intersection {
+        left: Box<ObjectDescription>,
+        right: Box<ObjectDescription>,
+    },
+    /// `left` with `right` cut out of it
+    Difference {
+        left: Box<ObjectDescription>,
+        right: Box<ObjectDescription>,
+    },
+}
+
+/// Pose of an animated instance at time 1; missing fields keep their starting value.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct PoseDescription {
+    translate: Option<Point>,
+    rotate: Option<Point>,
+    scale: Option<Scale>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(super) enum Scale {
+    Uniform(f64),
+    PerAxis([f64; 3]),
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self::Uniform(1.0)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(super) enum HeightMapDescription {
+    /// Grayscale image relative to the scene file, white being the highest
+    Image { path: PathBuf },
+    /// Fractal noise with `frequency` features across the terrain
+    Fbm {
+        resolution: [usize; 2],
+        frequency: f64,
+        #[serde(default = "default_fbm_octaves")]
+        octaves: usize,
+    },
+}
+
+fn default_fbm_octaves() -> usize {
+    6
+}
+
+fn default_capped() -> bool {
+    true
+}
+
+/// An object along with its emissive parts, in the object's own space.
+#[derive(Clone)]
+pub(super) struct BuiltObject {
+    pub(super) hittable: Arc<dyn Hittable>,
+    pub(super) lights: Vec<Arc<dyn Hittable>>,
+}
+
+/// State shared by all objects while building a scene.
+pub(super) struct ObjectContext<'a> {
+    pub(super) materials: HashMap<&'a str, Arc<dyn Material>>,
+    // Meshes by path and default material, loaded once and shared between instances
+    pub(super) meshes: HashMap<(PathBuf, Option<String>), BuiltObject>,
+}
+
+impl SceneFile {
+    /// Errors are prefixed with `key`, the object's path within the scene file.
+    pub(super) fn build_object(
+        &self,
+        object: &ObjectDescription,
+        key: &str,
+        context: &mut ObjectContext,
+    ) -> Result<BuiltObject, String> {
+        let materials = &context.materials;
+        let lookup_material = |name: &str| -> Result<Arc<dyn Material>, String> {
+            materials
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("{}.material: unknown material '{}'", key, name))
+        };
+        let require_positive = |field: &str, value: f64| {
+            if value > 0.0 {
+                Ok(())
+            } else {
+                Err(format!("{}.{}: must be positive", key, field))
+            }
+        };
+        let is_light = |name: &str| {
+            matches!(
+                self.materials.get(name),
+                Some(MaterialDescription::DiffuseLight { .. })
+            )
+        };
+
+        let hittable: Arc<dyn Hittable> = match object {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material,
+                center_end,
+            } => {
+                let sphere =
+                    Sphere::new(Vector3::from(*center), *radius, lookup_material(material)?);
+                match center_end {
+                    Some(center_end) => Arc::new(sphere.with_motion(Vector3::from(*center_end))),
+                    None => Arc::new(sphere),
+                }
+            }
+            ObjectDescription::Triangle {
+                vertices,
+                normals,
+                uvs,
+                material,
+            } => {
+                let [p0, p1, p2] = vertices.map(Vector3::from);
+                let mut triangle = Triangle::new(p0, p1, p2, lookup_material(material)?);
+                if let Some(normals) = normals {
+                    triangle = triangle.with_normals(normals.map(Vector3::from));
+                }
+                if let Some(uvs) = uvs {
+                    triangle = triangle.with_uvs(*uvs);
+                }
+                Arc::new(triangle)
+            }
+            ObjectDescription::Quad { q, u, v, material } => Arc::new(Quad::new(
+                Vector3::from(*q),
+                Vector3::from(*u),
+                Vector3::from(*v),
+                lookup_material(material)?,
+            )),
+            ObjectDescription::Plane {
+                point,
+                normal,
+                material,
+            } => Arc::new(Plane::new(
+                Vector3::from(*point),
+                Vector3::from(*normal),
+                lookup_material(material)?,
+            )),
+            ObjectDescription::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => Arc::new(Disk::new(
+                Vector3::from(*center),
+                Vector3::from(*normal),
+                *radius,
+                lookup_material(material)?,
+            )),
+            ObjectDescription::Box { min, max, material } => Arc::new(AxisAlignedBox::new(
+                Vector3::from(*min),
+                Vector3::from(*max),
+                lookup_material(material)?,
+            )),
+            ObjectDescription::Cylinder {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            } => {
+                require_positive("radius", *radius)?;
+                require_positive("height", *height)?;
+                Arc::new(Revolution::new(
+                    Cylinder::new(Vector3::from(*center), *radius, *height).with_caps(*capped),
+                    lookup_material(material)?,
+                ))
+            }
+            ObjectDescription::Cone {
+                center,
+                radius,
+                height,
+                capped,
+                material,
+            } => {
+                require_positive("radius", *radius)?;
+                require_positive("height", *height)?;
+                Arc::new(Revolution::new(
+                    Cone::new(Vector3::from(*center), *radius, *height).with_caps(*capped),
+                    lookup_material(material)?,
+                ))
+            }
+            ObjectDescription::Torus {
+                center,
+                major_radius,
+                minor_radius,
+                material,
+            } => {
+                require_positive("major_radius", *major_radius)?;
+                require_positive("minor_radius", *minor_radius)?;
+                Arc::new(Revolution::new(
+                    Torus::new(Vector3::from(*center), *major_radius, *minor_radius),
+                    lookup_material(material)?,
+                ))
+            }
+            ObjectDescription::Capsule {
+                center,
+                radius,
+                height,
+                material,
+            } => {
+                require_positive("radius", *radius)?;
+                require_positive("height", *height)?;
+                Arc::new(Revolution::new(
+                    Capsule::new(Vector3::from(*center), *radius, *height),
+                    lookup_material(material)?,
+                ))
+            }
+            ObjectDescription::Mesh { path, material } => {
+                let cache_key = (self.base_dir.join(path), material.clone());
+                if let Some(built) = context.meshes.get(&cache_key) {
+                    return Ok(built.clone());
+                }
+
+                let default_material = match material {
+                    Some(name) => lookup_material(name)?,
+                    None => Arc::new(Lambertian::from_color(Vector3::new(0.8, 0.8, 0.8))),
+                };
+                let model = obj::load_obj(&cache_key.0, default_material)
+                    .map_err(|e| format!("{}.path: {}", key, e))?;
+                let built = BuiltObject {
+                    hittable: Arc::new(model.meshes),
+                    lights: model.lights,
+                };
+                context.meshes.insert(cache_key, built.clone());
+                return Ok(built);
+            }
+            ObjectDescription::Sdf { field, material } => Arc::new(SdfObject::new(
+                build_distance_field(field, &format!("{}.field", key))?,
+                lookup_material(material)?,
+            )),
+            ObjectDescription::Heightfield {
+                map,
+                center,
+                size,
+                material,
+            } => {
+                if size.iter().any(|&s| s <= 0.0) {
+                    return Err(format!("{}.size: must be positive", key));
+                }
+                let map = match map {
+                    HeightMapDescription::Image { path } => {
+                        HeightMap::load(&self.base_dir.join(path))
+                            .map_err(|e| format!("{}.map.path: {}", key, e))?
+                    }
+                    HeightMapDescription::Fbm {
+                        resolution,
+                        frequency,
+                        octaves,
+                    } => {
+                        if resolution.iter().any(|&n| n < 2) {
+                            return Err(format!("{}.map.resolution: must be at least 2", key));
+                        }
+                        HeightMap::fbm(resolution[0], resolution[1], *frequency, *octaves)
+                    }
+                };
+                Arc::new(Heightfield::new(
+                    map,
+                    Vector3::from(*center),
+                    Vector3::from(*size),
+                    lookup_material(material)?,
+                ))
+            }
+            ObjectDescription::Union { left, right }
+            | ObjectDescription::Intersection { left, right }
+            | ObjectDescription::Difference { left, right } => {
+                let operation = match object {
+                    ObjectDescription::Union { .. } => Operation::Union,
+                    ObjectDescription::Intersection { .. } => Operation::Intersection,
+                    _ => Operation::Difference,
+                };
+                let left = self.build_object(left, &format!("{}.left", key), context)?;
+                let right = self.build_object(right, &format!("{}.right", key), context)?;
+                Arc::new(Csg::new(left.hittable, right.hittable, operation))
+            }
+            ObjectDescription::ConstantMedium { boundary, density } => {
+                self.build_medium(boundary, *density, None, key, context)?
+            }
+            ObjectDescription::HeterogeneousMedium {
+                boundary,
+                density,
+                field,
+            } => self.build_medium(boundary, *density, Some(field), key, context)?,
+            ObjectDescription::Instance {
+                object,
+                translate,
+                rotate,
+                scale,
+                matrix,
+                end,
+            } => {
+                let built = self.build_object(object, &format!("{}.object", key), context)?;
+
+                let pose = |translate: &Point, rotate: &Point, scale: &Scale| Pose {
+                    translation: Vector3::from(*translate),
+                    rotation: UnitQuaternion::from_euler_angles(
+                        rotate[0].to_radians(),
+                        rotate[1].to_radians(),
+                        rotate[2].to_radians(),
+                    ),
+                    scale: match scale {
+                        Scale::Uniform(s) => Vector3::repeat(*s),
+                        Scale::PerAxis(s) => Vector3::from(*s),
+                    },
+                };
+                let start = pose(translate, rotate, scale);
+                let post = matrix.map_or_else(Matrix4::identity, |rows| {
+                    Matrix4::from_row_iterator(rows.iter().flatten().copied())
+                });
+
+                if let Some(end) = end {
+                    let end = pose(
+                        end.translate.as_ref().unwrap_or(translate),
+                        end.rotate.as_ref().unwrap_or(rotate),
+                        end.scale.as_ref().unwrap_or(scale),
+                    );
+                    let animated = Transform::animated(built.hittable, start, end, post)
+                        .ok_or_else(|| format!("{}: transform is not invertible", key))?;
+                    return Ok(BuiltObject {
+                        hittable: Arc::new(animated),
+                        lights: Vec::new(),
+                    });
+                }
+
+                let to_world = post * start.matrix();
+                let transform = |object: Arc<dyn Hittable>| -> Result<Arc<dyn Hittable>, String> {
+                    match Transform::new(object, to_world) {
+                        Some(transform) => Ok(Arc::new(transform)),
+                        None => Err(format!("{}: transform is not invertible", key)),
+                    }
+                };
+                return Ok(BuiltObject {
+                    hittable: transform(built.hittable)?,
+                    lights: built
+                        .lights
+                        .into_iter()
+                        .map(transform)
+                        .collect::<Result<_, _>>()?,
+                });
+            }
+        };
+
+        let lights = match object {
+            // Their light can't be sampled, so it's only found by hitting them
+            ObjectDescription::Sphere {
+                center_end: Some(_),
+                ..
+            }
+            | ObjectDescription::Plane { .. }
+            | ObjectDescription::Sdf { .. } => Vec::new(),
+            _ if object.material().is_some_and(is_light) => vec![Arc::clone(&hittable)],
+            _ => Vec::new(),
+        };
+        Ok(BuiltObject { hittable, lights })
+    }
+}
+
+impl ObjectDescription {
+    /// Material of primitives made of a single surface.
+    pub(super) fn material(&self) -> Option<&str> {
+        match self {
+            Self::Sphere { material, .. }
+            | Self::Triangle { material, .. }
+            | Self::Quad { material, .. }
+            | Self::Plane { material, .. }
+            | Self::Disk { material, .. }
+            | Self::Box { material, .. }
+            | Self::Cylinder { material, .. }
+            | Self::Cone { material, .. }
+            | Self::Torus { material, .. }
+            | Self::Capsule { material, .. }
+            | Self::Sdf { material, .. } => Some(material),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use nalgebra::Vector3;
+use serde::Deserialize;
+
+use crate::texture::{
+    CheckerTexture, Filter, ImageTexture, MarbleTexture, SolidColor, Texture, UvCheckerTexture,
+    WrapMode,
+};
+
+use super::Color;
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(super) enum TextureDescription {
+    Solid {
+        color: Color,
+    },
+    Checker {
+        odd: Color,
+        even: Color,
+        scale: f64,
+    },
+    UvChecker {
+        odd: Color,
+        even: Color,
+        // Number of squares along u and v
+        frequency: [f64; 2],
+    },
+    Marble {
+        scale: f64,
+        base: Color,
+        vein: Color,
+    },
+    Image {
+        // Relative to the scene file
+        path: PathBuf,
+        #[serde(default)]
+        wrap: WrapMode,
+        #[serde(default)]
+        filter: Filter,
+        #[serde(default = "default_uv_scale")]
+        scale: [f64; 2],
+        #[serde(default)]
+        offset: [f64; 2],
+    },
+}
+
+fn default_uv_scale() -> [f64; 2] {
+    [1.0, 1.0]
+}
+
+/// Either an inline RGB triple or the name of an entry in `[textures]`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(super) enum ColorOrTexture {
+    Color(Color),
+    Texture(String),
+}
+
+/// Either a constant or the name of a texture whose mean channel is used.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub(super) enum ScalarOrTexture {
+    Scalar(f64),
+    Texture(String),
+}
+
+impl Default for ScalarOrTexture {
+    fn default() -> Self {
+        Self::Scalar(0.0)
+    }
+}
+
+/// Errors are returned relative to the texture's key, like `build_material`.
+pub(super) fn build_texture(
+    description: &TextureDescription,
+    base_dir: &Path,
+) -> Result<Arc<dyn Texture>, String> {
+    let texture: Arc<dyn Texture> = match description {
+        TextureDescription::Solid { color } => Arc::new(SolidColor::new(Vector3::from(*color))),
+        TextureDescription::Checker { odd, even, scale } => Arc::new(CheckerTexture::new(
+            Vector3::from(*odd),
+            Vector3::from(*even),
+            *scale,
+        )),
+        TextureDescription::UvChecker {
+            odd,
+            even,
+            frequency: [u_frequency, v_frequency],
+        } => Arc::new(UvCheckerTexture::new(
+            Vector3::from(*odd),
+            Vector3::from(*even),
+            *u_frequency,
+            *v_frequency,
+        )),
+        TextureDescription::Marble { scale, base, vein } => Arc::new(MarbleTexture::new(
+            *scale,
+            Vector3::from(*base),
+            Vector3::from(*vein),
+        )),
+        TextureDescription::Image {
+            path,
+            wrap,
+            filter,
+            scale,
+            offset,
+        } => {
+            let image =
+                ImageTexture::load(&base_dir.join(path)).map_err(|e| format!("path: {}", e))?;
+            Arc::new(
+                image
+                    .with_wrap(*wrap)
+                    .with_filter(*filter)
+                    .with_transform((scale[0], scale[1]), (offset[0], offset[1])),
+            )
+        }
+    };
+    Ok(texture)
+}
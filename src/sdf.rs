@@ -0,0 +1,338 @@
+use std::{f64::consts::PI, sync::Arc};
+
+use nalgebra::Vector3;
+
+use crate::{
+    aabb::AxisAlignedBoundingBox,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    sampling::Onb,
+};
+
+/// Signed distance to a surface: negative inside, positive outside. It may underestimate the
+/// distance, which only costs extra steps, but must never overestimate it.
+pub trait DistanceField: Send + Sync {
+    fn distance(&self, p: &Vector3<f64>) -> f64;
+
+    /// Region holding the whole surface, where sphere tracing starts and ends.
+    fn bounding_box(&self) -> AxisAlignedBoundingBox;
+}
+
+pub struct SphereSdf {
+    center: Vector3<f64>,
+    radius: f64,
+}
+
+impl SphereSdf {
+    pub fn new(center: Vector3<f64>, radius: f64) -> Self {
+        Self { center, radius }
+    }
+}
+
+impl DistanceField for SphereSdf {
+    fn distance(&self, p: &Vector3<f64>) -> f64 {
+        (p - self.center).magnitude() - self.radius
+    }
+
+    fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        let r = Vector3::repeat(self.radius);
+        AxisAlignedBoundingBox::new(self.center - r, self.center + r)
+    }
+}
+
+/// Box with edges rounded off by `rounding`, which stays within `half_extents`.
+pub struct RoundedBoxSdf {
+    center: Vector3<f64>,
+    half_extents: Vector3<f64>,
+    rounding: f64,
+}
+
+impl RoundedBoxSdf {
+    pub fn new(center: Vector3<f64>, half_extents: Vector3<f64>, rounding: f64) -> Self {
+        Self {
+            center,
+            half_extents,
+            rounding: rounding.clamp(0.0, half_extents.min()),
+        }
+    }
+}
+
+impl DistanceField for RoundedBoxSdf {
+    fn distance(&self, p: &Vector3<f64>) -> f64 {
+        let q = (p - self.center).abs() - self.half_extents.add_scalar(-self.rounding);
+        q.sup(&Vector3::zeros()).magnitude() + q.max().min(0.0) - self.rounding
+    }
+
+    fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        AxisAlignedBoundingBox::new(
+            self.center - self.half_extents,
+            self.center + self.half_extents,
+        )
+    }
+}
+
+/// Ring lying flat around `center`, like the analytic `Torus`.
+pub struct TorusSdf {
+    center: Vector3<f64>,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl TorusSdf {
+    pub fn new(center: Vector3<f64>, major_radius: f64, minor_radius: f64) -> Self {
+        Self {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl DistanceField for TorusSdf {
+    fn distance(&self, p: &Vector3<f64>) -> f64 {
+        let q = p - self.center;
+        (q.x.hypot(q.z) - self.major_radius).hypot(q.y) - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = Vector3::new(outer, self.minor_radius, outer);
+        AxisAlignedBoundingBox::new(self.center - extent, self.center + extent)
+    }
+}
+
+/// Points within `radius` of the segment from `a` to `b`.
+pub struct CapsuleSdf {
+    a: Vector3<f64>,
+    b: Vector3<f64>,
+    radius: f64,
+}
+
+impl CapsuleSdf {
+    pub fn new(a: Vector3<f64>, b: Vector3<f64>, radius: f64) -> Self {
+        Self { a, b, radius }
+    }
+}
+
+impl DistanceField for CapsuleSdf {
+    fn distance(&self, p: &Vector3<f64>) -> f64 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(&ba) / ba.magnitude_squared().max(1e-12)).clamp(0.0, 1.0);
+        (pa - h * ba).magnitude() - self.radius
+    }
+
+    fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        let r = Vector3::repeat(self.radius);
+        AxisAlignedBoundingBox::new(self.a.inf(&self.b) - r, self.a.sup(&self.b) + r)
+    }
+}
+
+/// The Mandelbulb fractal within a radius of about `scale` around `center`, using the
+/// distance estimate from the derivative of its iteration.
+pub struct Mandelbulb {
+    center: Vector3<f64>,
+    scale: f64,
+    power: f64,
+    iterations: u32,
+}
+
+impl Mandelbulb {
+    pub fn new(center: Vector3<f64>, scale: f64, power: f64, iterations: u32) -> Self {
+        Self {
+            center,
+            scale,
+            power,
+            iterations,
+        }
+    }
+}
+
+impl DistanceField for Mandelbulb {
+    fn distance(&self, p: &Vector3<f64>) -> f64 {
+        let c = (p - self.center) / self.scale;
+        let mut z = c;
+        let mut dr = 1.0;
+        let mut r = z.magnitude();
+
+        for _ in 0..self.iterations {
+            if !(1e-12..=2.0).contains(&r) {
+                break;
+            }
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            z = r.powf(self.power)
+                * Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                )
+                + c;
+            r = z.magnitude();
+        }
+
+        if r < 1e-12 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr * self.scale
+    }
+
+    fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        // Every power's bulb fits in a radius of about 1.2
+        let extent = Vector3::repeat(1.2 * self.scale);
+        AxisAlignedBoundingBox::new(self.center - extent, self.center + extent)
+    }
+}
+
+/// Union whose seams are filleted over a width of `smoothness`, so nearby shapes melt
+/// together like metaballs. A smoothness of 0 is the plain union.
+pub struct SmoothUnion {
+    a: Arc<dyn DistanceField>,
+    b: Arc<dyn DistanceField>,
+    smoothness: f64,
+}
+
+impl SmoothUnion {
+    pub fn new(a: Arc<dyn DistanceField>, b: Arc<dyn DistanceField>, smoothness: f64) -> Self {
+        Self { a, b, smoothness }
+    }
+}
+
+impl DistanceField for SmoothUnion {
+    fn distance(&self, p: &Vector3<f64>) -> f64 {
+        let (da, db) = (self.a.distance(p), self.b.distance(p));
+        if self.smoothness <= 0.0 {
+            return da.min(db);
+        }
+        // Polynomial smooth minimum
+        let h = (0.5 + 0.5 * (db - da) / self.smoothness).clamp(0.0, 1.0);
+        db + (da - db) * h - self.smoothness * h * (1.0 - h)
+    }
+
+    fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        // The fillet only adds material where both shapes are within `smoothness`
+        let bbox =
+            AxisAlignedBoundingBox::surrounding(&self.a.bounding_box(), &self.b.bounding_box());
+        let margin = Vector3::repeat(self.smoothness / 4.0);
+        AxisAlignedBoundingBox::new(bbox.min() - margin, bbox.max() + margin)
+    }
+}
+
+/// Morphs between two shapes, from `a` at a factor of 0 to `b` at 1.
+pub struct Blend {
+    a: Arc<dyn DistanceField>,
+    b: Arc<dyn DistanceField>,
+    factor: f64,
+}
+
+impl Blend {
+    pub fn new(a: Arc<dyn DistanceField>, b: Arc<dyn DistanceField>, factor: f64) -> Self {
+        Self {
+            a,
+            b,
+            factor: factor.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl DistanceField for Blend {
+    fn distance(&self, p: &Vector3<f64>) -> f64 {
+        (1.0 - self.factor) * self.a.distance(p) + self.factor * self.b.distance(p)
+    }
+
+    fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        AxisAlignedBoundingBox::surrounding(&self.a.bounding_box(), &self.b.bounding_box())
+    }
+}
+
+/// Distance below which sphere tracing considers the surface reached, in world units.
+const HIT_DISTANCE: f64 = 1e-5;
+const MAX_STEPS: usize = 512;
+
+/// Renders the zero level set of a `DistanceField` by sphere tracing: each step advances
+/// the ray by the distance to the nearest surface, which can't skip over any.
+///
+/// Normals come from the field's gradient. Texture coordinates are spherical around the
+/// center of the bounding box, like `Sphere`'s.
+pub struct SdfObject {
+    field: Arc<dyn DistanceField>,
+    bbox: AxisAlignedBoundingBox,
+    material: Arc<dyn Material>,
+}
+
+impl SdfObject {
+    pub fn new(field: Arc<dyn DistanceField>, material: Arc<dyn Material>) -> Self {
+        // Rounding errors in the field shouldn't clip the surface at the box's faces
+        let bbox = field.bounding_box();
+        let margin = Vector3::repeat(1e-3 * bbox.extent().max() + HIT_DISTANCE);
+        Self {
+            bbox: AxisAlignedBoundingBox::new(bbox.min() - margin, bbox.max() + margin),
+            field,
+            material,
+        }
+    }
+
+    /// Central differences of the field, which point away from the surface.
+    fn gradient(&self, p: &Vector3<f64>) -> Vector3<f64> {
+        let h = HIT_DISTANCE;
+        Vector3::from_fn(|axis, _| {
+            let mut offset = Vector3::zeros();
+            offset[axis] = h;
+            self.field.distance(&(p + offset)) - self.field.distance(&(p - offset))
+        })
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (start, end) = self.bbox.intersect(r, t_min, t_max)?;
+        let speed = r.direction().magnitude();
+
+        // Rays leaving the surface from inside march on the negated field to the exit
+        let side = self.field.distance(&r.at(start)).signum();
+        let mut t = start;
+        let mut steps = 0;
+        loop {
+            let distance = side * self.field.distance(&r.at(t));
+            if distance < HIT_DISTANCE {
+                break;
+            }
+            t += distance / speed;
+            steps += 1;
+            if t > end || steps == MAX_STEPS {
+                return None;
+            }
+        }
+
+        let p = r.at(t);
+        let outward_normal = self.gradient(&p).try_normalize(1e-300)?;
+        let frame = Onb::new(&outward_normal);
+
+        let direction = (p - self.bbox.centroid()).try_normalize(1e-12);
+        let (u, v) = direction.map_or((0.0, 0.0), |d| {
+            let theta = (-d.y).clamp(-1.0, 1.0).acos();
+            let phi = (-d.z).atan2(d.x) + PI;
+            (phi / (2.0 * PI), theta / PI)
+        });
+
+        let mut hit_record = HitRecord {
+            p,
+            normal: Vector3::new(0.0, 0.0, 0.0),
+            t,
+            u,
+            v,
+            dpdu: frame.local(&Vector3::x()),
+            dpdv: frame.local(&Vector3::y()),
+            front_face: false,
+            material: Arc::clone(&self.material),
+        };
+        hit_record.set_face_normal(r, outward_normal);
+        Some(hit_record)
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        Some(self.bbox)
+    }
+}
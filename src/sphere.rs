@@ -1,17 +1,21 @@
-use std::sync::Arc;
+use std::{f64::consts::PI, sync::Arc};
 
 use nalgebra::Vector3;
 
 use crate::{
+    aabb::AxisAlignedBoundingBox,
     hittable::{HitRecord, Hittable},
     material::Material,
     ray::Ray,
+    sampling::{cone_pdf, random_cone_direction, Onb},
 };
 
 pub struct Sphere {
     center: Vector3<f64>,
     radius: f64,
     material: Arc<dyn Material>,
+    // Displacement of the center from time 0 to time 1
+    motion: Vector3<f64>,
 }
 
 impl Sphere {
@@ -20,13 +24,60 @@ impl Sphere {
             center,
             radius,
             material,
+            motion: Vector3::new(0.0, 0.0, 0.0),
         }
     }
+
+    /// Moves the sphere linearly from `center` at time 0 to `center_end` at time 1.
+    /// Moving spheres can't be sampled as lights.
+    pub fn with_motion(mut self, center_end: Vector3<f64>) -> Self {
+        self.motion = center_end - self.center;
+        self
+    }
+
+    fn center(&self, time: f64) -> Vector3<f64> {
+        // Stay inside the bounding box, which only covers times 0 to 1
+        self.center + time.clamp(0.0, 1.0) * self.motion
+    }
+}
+
+impl Sphere {
+    /// Spherical coordinates of a point on the unit sphere, with tangents scaled by `radius`.
+    ///
+    /// `u` runs around the Y axis starting from -X, `v` from the bottom pole (-Y) to the top.
+    fn uv(unit: &Vector3<f64>, radius: f64) -> (f64, f64, Vector3<f64>, Vector3<f64>) {
+        let theta = (-unit.y).clamp(-1.0, 1.0).acos();
+        let phi = (-unit.z).atan2(unit.x) + PI;
+        let u = phi / (2.0 * PI);
+        let v = theta / PI;
+
+        let dpdu = 2.0 * PI * radius * Vector3::new(unit.z, 0.0, -unit.x);
+        let rho = (unit.x * unit.x + unit.z * unit.z).sqrt();
+        let dpdv = if rho > 1e-12 {
+            PI * radius * Vector3::new(-unit.x * unit.y / rho, rho, -unit.z * unit.y / rho)
+        } else {
+            // At the poles pick any direction perpendicular to dpdu's plane
+            PI * radius * Vector3::new(1.0, 0.0, 0.0)
+        };
+
+        (u, v, dpdu, dpdv)
+    }
+
+    /// Cosine of the half-angle subtended by the sphere, `None` from inside it.
+    fn cos_theta_max(&self, origin: &Vector3<f64>) -> Option<f64> {
+        let distance_squared = (self.center - origin).magnitude_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = r.origin() - self.center;
+        let center = self.center(r.time());
+        let oc = r.origin() - center;
         let a = r.direction().dot(&r.direction());
         let half_b = oc.dot(&r.direction());
         let c = oc.dot(&oc) - self.radius.powi(2);
@@ -47,15 +98,49 @@ impl Hittable for Sphere {
         }
 
         let p = r.at(root);
-        let outward_normal = (p - self.center) / self.radius;
+        let outward_normal = (p - center) / self.radius;
+        let (u, v, dpdu, dpdv) = Self::uv(&((p - center) / self.radius.abs()), self.radius.abs());
         let mut hit_record = HitRecord {
             p,
             normal: Vector3::new(0.0, 0.0, 0.0),
             t: root,
+            u,
+            v,
+            dpdu,
+            dpdv,
             front_face: false,
             material: Arc::clone(&self.material),
         };
         hit_record.set_face_normal(r, outward_normal);
         Some(hit_record)
     }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        // Radius may be negative for hollow spheres
+        let r = Vector3::repeat(self.radius.abs());
+        let start = AxisAlignedBoundingBox::new(self.center - r, self.center + r);
+        let end = AxisAlignedBoundingBox::new(self.center(1.0) - r, self.center(1.0) + r);
+        Some(AxisAlignedBoundingBox::surrounding(&start, &end))
+    }
+
+    fn pdf_value(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> f64 {
+        if self
+            .hit(&Ray::new(*origin, *direction, 0.0), 0.001, f64::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+        self.cos_theta_max(origin).map_or(0.0, cone_pdf)
+    }
+
+    fn random_direction(&self, origin: &Vector3<f64>) -> Vector3<f64> {
+        // Sample the cone of directions subtended by the sphere
+        let to_center = self.center - origin;
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => {
+                Onb::new(&to_center).local(&random_cone_direction(cos_theta_max))
+            }
+            None => to_center,
+        }
+    }
 }
@@ -1,6 +1,11 @@
+use std::path::Path;
+
+use image::DynamicImage;
 use nalgebra::Vector3;
 use noise::NoiseFn;
-use rand::random;
+use serde::Deserialize;
+
+use crate::utils::{random, srgb_to_linear};
 
 pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Vector3<f64>) -> Vector3<f64>;
@@ -54,6 +59,54 @@ impl Texture for CheckerTexture {
     }
 }
 
+/// Checker pattern laid out in texture space rather than world space.
+pub struct UvCheckerTexture {
+    odd: Box<dyn Texture>,
+    even: Box<dyn Texture>,
+    u_frequency: f64,
+    v_frequency: f64,
+}
+
+impl UvCheckerTexture {
+    pub fn new(c1: Vector3<f64>, c2: Vector3<f64>, u_frequency: f64, v_frequency: f64) -> Self {
+        Self {
+            odd: Box::new(SolidColor::new(c1)),
+            even: Box::new(SolidColor::new(c2)),
+            u_frequency,
+            v_frequency,
+        }
+    }
+}
+
+impl Texture for UvCheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Vector3<f64>) -> Vector3<f64> {
+        let u_int = (self.u_frequency * u).floor() as i32;
+        let v_int = (self.v_frequency * v).floor() as i32;
+
+        if (u_int + v_int) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// Sum of `octaves` layers of Perlin noise, each at twice the frequency and half the weight
+/// of the previous one, folded to positive values. It never exceeds the sum of the weights.
+pub fn turbulence(noise: &noise::Perlin, p: &Vector3<f64>, octaves: u32) -> f64 {
+    let mut accum = 0.0;
+    let mut temp_p = *p;
+    let mut weight = 1.0;
+
+    for _ in 0..octaves {
+        accum += weight * noise.get([temp_p.x, temp_p.y, temp_p.z]);
+        weight *= 0.5;
+        temp_p *= 2.0;
+    }
+
+    accum.abs()
+}
+
 pub struct MarbleTexture {
     noise: noise::Perlin,
     scale: f64,
@@ -70,30 +123,12 @@ impl MarbleTexture {
             color2,
         }
     }
-
-    fn turbulence(&self, p: &Vector3<f64>, depth: i32) -> f64 {
-        let mut accum = 0.0;
-        let mut temp_p = *p;
-        let mut weight = 1.0;
-
-        for _ in 0..depth {
-            accum += weight
-                * self.noise.get([
-                    temp_p.x * self.scale,
-                    temp_p.y * self.scale,
-                    temp_p.z * self.scale,
-                ]);
-            weight *= 0.5;
-            temp_p *= 2.0;
-        }
-
-        accum.abs()
-    }
 }
 
 impl Texture for MarbleTexture {
     fn value(&self, _u: f64, _v: f64, p: &Vector3<f64>) -> Vector3<f64> {
-        let marble_pattern = (self.scale * p.x + 4.0 * self.turbulence(p, 7)).sin();
+        let marble_pattern =
+            (self.scale * p.x + 4.0 * turbulence(&self.noise, &(p * self.scale), 7)).sin();
 
         let t = (marble_pattern + 1.0) / 2.0;
         let t = t.powf(1.5);
@@ -101,3 +136,145 @@ impl Texture for MarbleTexture {
         self.color1 * (1.0 - t) + self.color2 * t
     }
 }
+
+/// How texel lookups outside the image are resolved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl WrapMode {
+    fn wrap(&self, i: i64, size: usize) -> usize {
+        let n = size as i64;
+        let wrapped = match self {
+            Self::Repeat => i.rem_euclid(n),
+            Self::Clamp => i.clamp(0, n - 1),
+            Self::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m >= n {
+                    2 * n - 1 - m
+                } else {
+                    m
+                }
+            }
+        };
+        wrapped as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    texels: Vec<Vector3<f64>>, // Linear RGB, row by row from the top
+    wrap: WrapMode,
+    filter: Filter,
+    scale: (f64, f64),
+    offset: (f64, f64),
+}
+
+impl ImageTexture {
+    /// Loads any format the `image` crate can decode. 8/16-bit images are assumed to be
+    /// sRGB encoded and converted to linear, float images (EXR, HDR) are used as is.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let image = image::open(path)
+            .map_err(|e| format!("Failed to load texture '{}': {}", path.display(), e))?;
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+
+        // Float formats already hold linear radiance
+        let is_linear = matches!(
+            image,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+        let decode = |c: f32| {
+            if is_linear {
+                c as f64
+            } else {
+                srgb_to_linear(c as f64)
+            }
+        };
+        let texels = image
+            .to_rgb32f()
+            .pixels()
+            .map(|p| Vector3::new(decode(p[0]), decode(p[1]), decode(p[2])))
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            texels,
+            wrap: WrapMode::default(),
+            filter: Filter::default(),
+            scale: (1.0, 1.0),
+            offset: (0.0, 0.0),
+        })
+    }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Transforms lookups as `uv * scale + offset`, e.g. a scale of 4 tiles the image 4 times.
+    pub fn with_transform(mut self, scale: (f64, f64), offset: (f64, f64)) -> Self {
+        self.scale = scale;
+        self.offset = offset;
+        self
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Vector3<f64> {
+        let x = self.wrap.wrap(x, self.width);
+        let y = self.wrap.wrap(y, self.height);
+        self.texels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Vector3<f64>) -> Vector3<f64> {
+        if self.texels.is_empty() {
+            return Vector3::new(0.0, 1.0, 1.0); // Cyan makes missing data obvious
+        }
+
+        let u = u * self.scale.0 + self.offset.0;
+        let v = v * self.scale.1 + self.offset.1;
+
+        // Image rows run top to bottom while v runs bottom to top
+        let x = u * self.width as f64;
+        let y = (1.0 - v) * self.height as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // Texel centers sit at half-integer coordinates
+                let x = x - 0.5;
+                let y = y - 0.5;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let tx = x - x0;
+                let ty = y - y0;
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = self.texel(x0, y0) * (1.0 - tx) + self.texel(x0 + 1, y0) * tx;
+                let bottom = self.texel(x0, y0 + 1) * (1.0 - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+                top * (1.0 - ty) + bottom * ty
+            }
+        }
+    }
+}
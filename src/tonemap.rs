@@ -0,0 +1,132 @@
+use clap::ValueEnum;
+use nalgebra::Vector3;
+use serde::Deserialize;
+
+/// Curve used to compress scene radiance into the displayable [0, 1] range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMapper {
+    /// Hard clip at 1
+    #[default]
+    None,
+    Reinhard,
+    /// Reinhard that maps `white_point` (and anything brighter) to 1
+    ReinhardExtended,
+    /// Narkowicz's fit of the ACES filmic curve
+    Aces,
+    /// John Hable's filmic curve from Uncharted 2
+    Uncharted2,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ToneMapping {
+    pub operator: ToneMapper,
+    /// Exposure compensation in stops
+    pub exposure: f64,
+    pub white_point: f64,
+}
+
+fn uncharted2_partial(x: f64) -> f64 {
+    const A: f64 = 0.15; // Shoulder strength
+    const B: f64 = 0.50; // Linear strength
+    const C: f64 = 0.10; // Linear angle
+    const D: f64 = 0.20; // Toe strength
+    const E: f64 = 0.02; // Toe numerator
+    const F: f64 = 0.30; // Toe denominator
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+impl ToneMapping {
+    /// Maps linear scene radiance to linear display values in [0, 1].
+    pub fn apply(&self, color: &Vector3<f64>) -> Vector3<f64> {
+        let exposed = color.map(|c| c.max(0.0)) * 2f64.powf(self.exposure);
+
+        let mapped = match self.operator {
+            ToneMapper::None => exposed,
+            ToneMapper::Reinhard => exposed.map(|c| c / (1.0 + c)),
+            ToneMapper::ReinhardExtended => {
+                let white_squared = self.white_point * self.white_point;
+                exposed.map(|c| c * (1.0 + c / white_squared) / (1.0 + c))
+            }
+            ToneMapper::Aces => {
+                exposed.map(|c| (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14))
+            }
+            ToneMapper::Uncharted2 => {
+                const EXPOSURE_BIAS: f64 = 2.0;
+                const WHITE: f64 = 11.2;
+                let white_scale = 1.0 / uncharted2_partial(WHITE);
+                exposed.map(|c| uncharted2_partial(c * EXPOSURE_BIAS) * white_scale)
+            }
+        };
+
+        mapped.map(|c| c.clamp(0.0, 1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [ToneMapper; 5] = [
+        ToneMapper::None,
+        ToneMapper::Reinhard,
+        ToneMapper::ReinhardExtended,
+        ToneMapper::Aces,
+        ToneMapper::Uncharted2,
+    ];
+
+    fn map(operator: ToneMapper, exposure: f64, value: f64) -> f64 {
+        let tone_mapping = ToneMapping {
+            operator,
+            exposure,
+            white_point: 4.0,
+        };
+        tone_mapping.apply(&Vector3::repeat(value)).x
+    }
+
+    #[test]
+    fn black_stays_black() {
+        for operator in OPERATORS {
+            assert!(map(operator, 0.0, 0.0).abs() < 1e-12, "{:?}", operator);
+            assert_eq!(map(operator, 0.0, -1.0), map(operator, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn output_stays_within_display_range() {
+        for operator in OPERATORS {
+            for value in [1.0, 4.0, 1e3, 1e12] {
+                let mapped = map(operator, 0.0, value);
+                assert!((0.0..=1.0).contains(&mapped), "{:?}({})", operator, value);
+            }
+        }
+    }
+
+    #[test]
+    fn clipping_and_white_point() {
+        assert_eq!(map(ToneMapper::None, 0.0, 0.5), 0.5);
+        assert_eq!(map(ToneMapper::None, 0.0, 1.0), 1.0);
+        assert_eq!(map(ToneMapper::None, 0.0, 2.0), 1.0);
+        assert_eq!(map(ToneMapper::Reinhard, 0.0, 1.0), 0.5);
+        assert!((map(ToneMapper::ReinhardExtended, 0.0, 4.0) - 1.0).abs() < 1e-12);
+        assert_eq!(map(ToneMapper::ReinhardExtended, 0.0, 8.0), 1.0);
+    }
+
+    #[test]
+    fn operators_are_monotonic() {
+        for operator in OPERATORS {
+            let mut previous = 0.0;
+            for i in 0..=2000 {
+                let mapped = map(operator, 0.0, i as f64 * 0.01);
+                assert!(mapped >= previous, "{:?} decreases at {}", operator, i);
+                previous = mapped;
+            }
+        }
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        assert_eq!(map(ToneMapper::None, 1.0, 0.25), 0.5);
+        assert_eq!(map(ToneMapper::None, -2.0, 0.5), 0.125);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{borrow::Cow, sync::Arc};
+
+use nalgebra::{Matrix3, Matrix4, Point3, UnitQuaternion, Vector3};
+
+use crate::{
+    aabb::AxisAlignedBoundingBox,
+    hittable::{HitRecord, Hittable},
+    ray::Ray,
+};
+
+/// Translation, rotation and scale of an object at one instant.
+#[derive(Clone, Copy, Debug)]
+pub struct Pose {
+    pub translation: Vector3<f64>,
+    pub rotation: UnitQuaternion<f64>,
+    pub scale: Vector3<f64>,
+}
+
+impl Pose {
+    /// Scales, then rotates, then translates.
+    pub fn matrix(&self) -> Matrix4<f64> {
+        Matrix4::new_translation(&self.translation)
+            * self.rotation.to_homogeneous()
+            * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+
+    /// Interpolates each component separately, so rotations don't shear midway.
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        let rotation = self
+            .rotation
+            .try_slerp(&other.rotation, t, 1e-9)
+            // Opposite rotations have no unique shortest path
+            .unwrap_or(if t < 0.5 {
+                self.rotation
+            } else {
+                other.rotation
+            });
+        Self {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation,
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Matrices {
+    to_world: Matrix4<f64>,
+    to_object: Matrix4<f64>,
+    // Inverse transpose of the linear part, for normals
+    normal_matrix: Matrix3<f64>,
+}
+
+impl Matrices {
+    fn new(to_world: Matrix4<f64>) -> Option<Self> {
+        let to_object = to_world.try_inverse()?;
+        Some(Self {
+            to_world,
+            to_object,
+            normal_matrix: to_object.fixed_view::<3, 3>(0, 0).transpose(),
+        })
+    }
+
+    fn point_to_object(&self, p: &Vector3<f64>) -> Vector3<f64> {
+        self.to_object.transform_point(&Point3::from(*p)).coords
+    }
+
+    /// The direction isn't renormalized, so `t` is the same in both spaces.
+    fn ray_to_object(&self, r: &Ray) -> Ray {
+        Ray::new(
+            self.point_to_object(&r.origin()),
+            self.to_object.transform_vector(&r.direction()),
+            r.time(),
+        )
+    }
+
+    /// The object's box with its corners transformed to world space, then bounded again.
+    fn bound(&self, bbox: &AxisAlignedBoundingBox) -> AxisAlignedBoundingBox {
+        let (min, max) = (bbox.min(), bbox.max());
+        let corner = |i: usize| {
+            let pick = |axis: usize| {
+                if i >> axis & 1 == 1 {
+                    max[axis]
+                } else {
+                    min[axis]
+                }
+            };
+            let p = Point3::new(pick(0), pick(1), pick(2));
+            self.to_world.transform_point(&p).coords
+        };
+        (1..8).fold(
+            AxisAlignedBoundingBox::new(corner(0), corner(0)),
+            |acc, i| {
+                AxisAlignedBoundingBox::surrounding(
+                    &acc,
+                    &AxisAlignedBoundingBox::new(corner(i), corner(i)),
+                )
+            },
+        )
+    }
+}
+
+enum Motion {
+    Static(Matrices),
+    /// `post * pose.matrix()`, with the pose moving from `start` at time 0 to `end` at time 1
+    Animated {
+        start: Pose,
+        end: Pose,
+        post: Matrix4<f64>,
+    },
+}
+
+/// Places an object in the world with an affine transform. Rays are moved into object space
+/// instead of transforming the geometry, so many instances can share one object.
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    motion: Motion,
+    bbox: Option<AxisAlignedBoundingBox>,
+}
+
+/// Times at which an animated transform's bounding box is evaluated.
+const MOTION_BOUND_STEPS: usize = 32;
+
+impl Transform {
+    /// `None` if `to_world` is not invertible.
+    pub fn new(object: Arc<dyn Hittable>, to_world: Matrix4<f64>) -> Option<Self> {
+        let matrices = Matrices::new(to_world)?;
+        let bbox = object.bounding_box().map(|bbox| matrices.bound(&bbox));
+        Some(Self {
+            object,
+            motion: Motion::Static(matrices),
+            bbox,
+        })
+    }
+
+    /// Moves the object from `start` at time 0 to `end` at time 1, followed by a fixed
+    /// `post` transform. `None` if the transform isn't invertible at some point in between.
+    pub fn animated(
+        object: Arc<dyn Hittable>,
+        start: Pose,
+        end: Pose,
+        post: Matrix4<f64>,
+    ) -> Option<Self> {
+        let mut transform = Self {
+            object,
+            motion: Motion::Animated { start, end, post },
+            bbox: None,
+        };
+
+        // Rotations sweep along arcs, so bound the object at many instants of the motion
+        let mut bbox: Option<AxisAlignedBoundingBox> = None;
+        for step in 0..=MOTION_BOUND_STEPS {
+            let matrices = transform.matrices(step as f64 / MOTION_BOUND_STEPS as f64)?;
+            if let Some(object_bbox) = transform.object.bounding_box() {
+                let moved = matrices.bound(&object_bbox);
+                bbox = Some(match bbox {
+                    Some(bbox) => AxisAlignedBoundingBox::surrounding(&bbox, &moved),
+                    None => moved,
+                });
+            }
+        }
+        // A small margin covers the arcs between the sampled instants
+        transform.bbox = bbox.map(|bbox| {
+            let margin = Vector3::repeat(0.01 * bbox.extent().max());
+            AxisAlignedBoundingBox::new(bbox.min() - margin, bbox.max() + margin)
+        });
+        Some(transform)
+    }
+
+    fn matrices(&self, time: f64) -> Option<Cow<'_, Matrices>> {
+        match &self.motion {
+            Motion::Static(matrices) => Some(Cow::Borrowed(matrices)),
+            Motion::Animated { start, end, post } => {
+                let pose = start.interpolate(end, time.clamp(0.0, 1.0));
+                Matrices::new(post * pose.matrix()).map(Cow::Owned)
+            }
+        }
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let matrices = self.matrices(r.time())?;
+        let mut record = self.object.hit(&matrices.ray_to_object(r), t_min, t_max)?;
+
+        // Facing is preserved: dot(M d, M^-T n) = dot(d, n)
+        record.p = matrices
+            .to_world
+            .transform_point(&Point3::from(record.p))
+            .coords;
+        record.normal = (matrices.normal_matrix * record.normal).normalize();
+        record.dpdu = matrices.to_world.transform_vector(&record.dpdu);
+        record.dpdv = matrices.to_world.transform_vector(&record.dpdv);
+        Some(record)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        match self.matrices(r.time()) {
+            Some(matrices) => self
+                .object
+                .transmittance(&matrices.ray_to_object(r), t_min, t_max),
+            None => 1.0,
+        }
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        self.bbox
+    }
+
+    // Light sampling has no notion of time, so only static transforms support it
+
+    fn pdf_value(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> f64 {
+        let Motion::Static(matrices) = &self.motion else {
+            return 0.0;
+        };
+        let local_direction = matrices.to_object.transform_vector(&direction.normalize());
+        let pdf = self
+            .object
+            .pdf_value(&matrices.point_to_object(origin), &local_direction);
+
+        // Jacobian of mapping world directions to object space directions, which is 1
+        // for rotations and uniform scales
+        let linear = matrices.to_object.fixed_view::<3, 3>(0, 0);
+        pdf * linear.determinant().abs() / local_direction.magnitude().powi(3)
+    }
+
+    fn random_direction(&self, origin: &Vector3<f64>) -> Vector3<f64> {
+        let Motion::Static(matrices) = &self.motion else {
+            return Vector3::new(1.0, 0.0, 0.0);
+        };
+        let local = self
+            .object
+            .random_direction(&matrices.point_to_object(origin));
+        matrices.to_world.transform_vector(&local)
+    }
+}
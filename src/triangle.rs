@@ -0,0 +1,423 @@
+use std::sync::Arc;
+
+use nalgebra::Vector3;
+
+use crate::{
+    aabb::AxisAlignedBoundingBox,
+    bvh::BvhNode,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray,
+    utils::random_double,
+};
+
+const BBOX_PADDING: f64 = 1e-4;
+
+/// Möller–Trumbore intersection, returning `t` and the barycentric weights of `p1` and `p2`.
+pub fn intersect(
+    r: &Ray,
+    t_min: f64,
+    t_max: f64,
+    p0: &Vector3<f64>,
+    p1: &Vector3<f64>,
+    p2: &Vector3<f64>,
+) -> Option<(f64, f64, f64)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = r.direction().cross(&edge2);
+    let det = edge1.dot(&pvec);
+
+    // Ray is parallel to the triangle plane
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let tvec = r.origin() - p0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&edge1);
+    let b2 = r.direction().dot(&qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&qvec) * inv_det;
+    if t < t_min || t_max < t {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+fn interpolate<T>(values: [T; 3], b1: f64, b2: f64) -> T
+where
+    T: std::ops::Mul<f64, Output = T> + std::ops::Add<Output = T>,
+{
+    let [a, b, c] = values;
+    a * (1.0 - b1 - b2) + b * b1 + c * b2
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn hit_record(
+    r: &Ray,
+    t: f64,
+    b1: f64,
+    b2: f64,
+    positions: [Vector3<f64>; 3],
+    normals: Option<[Vector3<f64>; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: &Arc<dyn Material>,
+) -> HitRecord {
+    let [p0, p1, p2] = positions;
+    let geometric_normal = (p1 - p0).cross(&(p2 - p0)).normalize();
+
+    // Without explicit UVs the barycentric coordinates double as surface parameters
+    let [uv0, uv1, uv2] = uvs.unwrap_or([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+    let u = interpolate([uv0.0, uv1.0, uv2.0], b1, b2);
+    let v = interpolate([uv0.1, uv1.1, uv2.1], b1, b2);
+
+    // Solve the edge vectors for the position derivatives along u and v
+    let (edge1, edge2) = (p1 - p0, p2 - p0);
+    let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+    let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+    let determinant = du1 * dv2 - dv1 * du2;
+    let (dpdu, dpdv) = if determinant.abs() > 1e-12 {
+        (
+            (dv2 * edge1 - dv1 * edge2) / determinant,
+            (du1 * edge2 - du2 * edge1) / determinant,
+        )
+    } else {
+        (edge1, edge2)
+    };
+
+    let mut hit_record = HitRecord {
+        p: r.at(t),
+        normal: Vector3::new(0.0, 0.0, 0.0),
+        t,
+        u,
+        v,
+        dpdu,
+        dpdv,
+        front_face: false,
+        material: Arc::clone(material),
+    };
+    hit_record.set_face_normal(r, geometric_normal);
+
+    if let Some(normals) = normals {
+        // Keep the shading normal on the same side as the geometric one
+        if let Some(shading_normal) = interpolate(normals, b1, b2).try_normalize(1e-12) {
+            hit_record.normal = if shading_normal.dot(&hit_record.normal) < 0.0 {
+                -shading_normal
+            } else {
+                shading_normal
+            };
+        }
+    }
+
+    hit_record
+}
+
+fn triangle_bounding_box(positions: &[Vector3<f64>; 3]) -> AxisAlignedBoundingBox {
+    let [p0, p1, p2] = positions;
+    AxisAlignedBoundingBox::surrounding(
+        &AxisAlignedBoundingBox::new(*p0, *p1),
+        &AxisAlignedBoundingBox::new(*p2, *p2),
+    )
+    .padded(BBOX_PADDING)
+}
+
+/// Solid angle density of sampling `direction` by picking a uniform point on the triangle.
+fn triangle_pdf_value(
+    positions: &[Vector3<f64>; 3],
+    origin: &Vector3<f64>,
+    direction: &Vector3<f64>,
+) -> f64 {
+    let [p0, p1, p2] = positions;
+    let Some((t, _, _)) = intersect(
+        &Ray::new(*origin, *direction, 0.0),
+        0.001,
+        f64::INFINITY,
+        p0,
+        p1,
+        p2,
+    ) else {
+        return 0.0;
+    };
+
+    let cross = (p1 - p0).cross(&(p2 - p0));
+    let area = 0.5 * cross.magnitude();
+    let distance_squared = t * t * direction.magnitude_squared();
+    let cosine = (direction.dot(&cross) / (direction.magnitude() * cross.magnitude())).abs();
+    if cosine < 1e-8 {
+        return 0.0;
+    }
+
+    distance_squared / (cosine * area)
+}
+
+fn triangle_random_direction(positions: &[Vector3<f64>; 3], origin: &Vector3<f64>) -> Vector3<f64> {
+    let [p0, p1, p2] = positions;
+    let r1 = random_double().sqrt();
+    let r2 = random_double();
+    let point = p0 * (1.0 - r1) + p1 * (r1 * (1.0 - r2)) + p2 * (r1 * r2);
+    point - origin
+}
+
+pub struct Triangle {
+    positions: [Vector3<f64>; 3],
+    normals: Option<[Vector3<f64>; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(
+        p0: Vector3<f64>,
+        p1: Vector3<f64>,
+        p2: Vector3<f64>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            positions: [p0, p1, p2],
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    pub fn with_normals(mut self, normals: [Vector3<f64>; 3]) -> Self {
+        // Zero-length normals leave the shading to the geometric normal
+        self.normals = Some(normals.map(|n| n.try_normalize(1e-12).unwrap_or_else(Vector3::zeros)));
+        self
+    }
+
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let [p0, p1, p2] = &self.positions;
+        let (t, b1, b2) = intersect(r, t_min, t_max, p0, p1, p2)?;
+        Some(hit_record(
+            r,
+            t,
+            b1,
+            b2,
+            self.positions,
+            self.normals,
+            self.uvs,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        Some(triangle_bounding_box(&self.positions))
+    }
+
+    fn pdf_value(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> f64 {
+        triangle_pdf_value(&self.positions, origin, direction)
+    }
+
+    fn random_direction(&self, origin: &Vector3<f64>) -> Vector3<f64> {
+        triangle_random_direction(&self.positions, origin)
+    }
+}
+
+/// Vertex and index buffers shared by every triangle of a mesh.
+struct MeshData {
+    positions: Vec<Vector3<f64>>,
+    normals: Option<Vec<Vector3<f64>>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    indices: Vec<[usize; 3]>,
+    material: Arc<dyn Material>,
+}
+
+impl MeshData {
+    fn gather<T: Copy>(values: &[T], [a, b, c]: [usize; 3]) -> [T; 3] {
+        [values[a], values[b], values[c]]
+    }
+}
+
+/// A single face of a `TriangleMesh`, referring back into the shared buffers.
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+}
+
+impl MeshTriangle {
+    fn positions(&self) -> [Vector3<f64>; 3] {
+        MeshData::gather(&self.mesh.positions, self.mesh.indices[self.face])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let indices = self.mesh.indices[self.face];
+        let positions = self.positions();
+        let [p0, p1, p2] = &positions;
+        let (t, b1, b2) = intersect(r, t_min, t_max, p0, p1, p2)?;
+
+        let normals = self
+            .mesh
+            .normals
+            .as_ref()
+            .map(|normals| MeshData::gather(normals, indices));
+        let uvs = self
+            .mesh
+            .uvs
+            .as_ref()
+            .map(|uvs| MeshData::gather(uvs, indices));
+
+        Some(hit_record(
+            r,
+            t,
+            b1,
+            b2,
+            positions,
+            normals,
+            uvs,
+            &self.mesh.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        Some(triangle_bounding_box(&self.positions()))
+    }
+
+    fn pdf_value(&self, origin: &Vector3<f64>, direction: &Vector3<f64>) -> f64 {
+        triangle_pdf_value(&self.positions(), origin, direction)
+    }
+
+    fn random_direction(&self, origin: &Vector3<f64>) -> Vector3<f64> {
+        triangle_random_direction(&self.positions(), origin)
+    }
+}
+
+/// Normalizes vertex normals. Zero-length ones, which some exporters write for vertices they
+/// couldn't shade, are replaced by the area-weighted average of the adjacent face normals.
+fn unit_normals(
+    normals: Vec<Vector3<f64>>,
+    positions: &[Vector3<f64>],
+    indices: &[[usize; 3]],
+) -> Vec<Vector3<f64>> {
+    let mut face_sums: Option<Vec<Vector3<f64>>> = None;
+    normals
+        .into_iter()
+        .enumerate()
+        .map(|(vertex, normal)| {
+            normal.try_normalize(1e-12).unwrap_or_else(|| {
+                let sums = face_sums.get_or_insert_with(|| {
+                    let mut sums = vec![Vector3::zeros(); positions.len()];
+                    for &[a, b, c] in indices {
+                        let area_normal =
+                            (positions[b] - positions[a]).cross(&(positions[c] - positions[a]));
+                        for i in [a, b, c] {
+                            sums[i] += area_normal;
+                        }
+                    }
+                    sums
+                });
+                // Vertices only on degenerate faces are never hit, any value will do
+                sums[vertex]
+                    .try_normalize(1e-300)
+                    .unwrap_or_else(Vector3::y)
+            })
+        })
+        .collect()
+}
+
+pub struct TriangleMesh {
+    faces: Vec<Arc<dyn Hittable>>,
+    root: Option<BvhNode>,
+}
+
+impl TriangleMesh {
+    /// Builds a mesh from indexed vertex buffers. `normals` and `uvs`, when present,
+    /// are indexed the same way as `positions`.
+    pub fn new(
+        positions: Vec<Vector3<f64>>,
+        indices: Vec<[usize; 3]>,
+        normals: Option<Vec<Vector3<f64>>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        material: Arc<dyn Material>,
+    ) -> Result<Self, String> {
+        let vertex_count = positions.len();
+        if let Some((face, &index)) = indices.iter().enumerate().find_map(|(face, corners)| {
+            corners
+                .iter()
+                .find(|&&i| i >= vertex_count)
+                .map(|i| (face, i))
+        }) {
+            return Err(format!(
+                "face {} refers to vertex {}, but there are only {} vertices",
+                face, index, vertex_count
+            ));
+        }
+        if let Some(normals) = &normals {
+            if normals.len() != vertex_count {
+                return Err(format!(
+                    "{} normals for {} vertices",
+                    normals.len(),
+                    vertex_count
+                ));
+            }
+        }
+        if let Some(uvs) = &uvs {
+            if uvs.len() != vertex_count {
+                return Err(format!("{} UVs for {} vertices", uvs.len(), vertex_count));
+            }
+        }
+
+        let normals = normals.map(|normals| unit_normals(normals, &positions, &indices));
+        let face_count = indices.len();
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+        });
+
+        let triangles: Vec<Arc<dyn Hittable>> = (0..face_count)
+            .map(|face| {
+                Arc::new(MeshTriangle {
+                    mesh: Arc::clone(&mesh),
+                    face,
+                }) as Arc<dyn Hittable>
+            })
+            .collect();
+
+        Ok(Self {
+            root: (!triangles.is_empty()).then(|| BvhNode::new(triangles.clone())),
+            faces: triangles,
+        })
+    }
+
+    /// The individual triangles, e.g. to register an emissive mesh face by face as lights.
+    pub fn faces(&self) -> &[Arc<dyn Hittable>] {
+        &self.faces
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.root.as_ref()?.hit(r, t_min, t_max)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f64, t_max: f64) -> f64 {
+        self.root
+            .as_ref()
+            .map_or(1.0, |root| root.transmittance(r, t_min, t_max))
+    }
+
+    fn bounding_box(&self) -> Option<AxisAlignedBoundingBox> {
+        self.root.as_ref()?.bounding_box()
+    }
+}
@@ -1,3 +1,74 @@
-pub fn linear_to_gamma(linear_component: f64) -> f64 {
-    linear_component.sqrt()
+use std::cell::RefCell;
+
+use rand::{distributions::Standard, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+
+thread_local! {
+    // Per-thread generator so renders can be reproduced from a seed
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Piecewise sRGB transfer function for a linear component in [0, 1].
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    if linear_component <= 0.0031308 {
+        12.92 * linear_component
+    } else {
+        1.055 * linear_component.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of `linear_to_srgb`, for decoding 8-bit texture data.
+pub fn srgb_to_linear(srgb_component: f64) -> f64 {
+    if srgb_component <= 0.04045 {
+        srgb_component / 12.92
+    } else {
+        ((srgb_component + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Reseeds the calling thread's generator.
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+pub fn random<T>() -> T
+where
+    Standard: Distribution<T>,
+{
+    RNG.with(|rng| rng.borrow_mut().gen())
+}
+
+pub fn random_double() -> f64 {
+    random::<f64>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_endpoints() {
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-12);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn srgb_segments_meet_at_the_threshold() {
+        let threshold = 0.0031308;
+        assert!((linear_to_srgb(threshold) - 0.04045).abs() < 1e-6);
+        assert!((linear_to_srgb(threshold) - linear_to_srgb(threshold + 1e-12)).abs() < 1e-6);
+        assert!((srgb_to_linear(0.04045) - threshold).abs() < 1e-6);
+    }
+
+    #[test]
+    fn srgb_is_monotonic_and_invertible() {
+        let mut previous = linear_to_srgb(0.0);
+        for i in 1..=1000 {
+            let linear = i as f64 / 1000.0;
+            let encoded = linear_to_srgb(linear);
+            assert!(encoded > previous, "decreases at {}", linear);
+            assert!((srgb_to_linear(encoded) - linear).abs() < 1e-9);
+            previous = encoded;
+        }
+    }
 }